@@ -0,0 +1,461 @@
+use std::{
+    fs,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::squashfs;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+/// Size of a section header entry in 32-bit and 64-bit ELF files
+const SHENTSIZE_32: u16 = 0x28;
+const SHENTSIZE_64: u16 = 0x40;
+
+struct ElfHeader {
+    is_64: bool,
+    little_endian: bool,
+    machine: u16,
+    shoff: u64,
+    shentsize: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+struct Section {
+    name_offset: u32,
+    offset: u64,
+    size: u64,
+}
+
+impl ElfHeader {
+    fn read(file: &mut impl Read) -> Result<Self, String> {
+        let mut ident = [0u8; 64];
+        file.read_exact(&mut ident)
+            .map_err(|_| "File is too small to be an AppImage".to_string())?;
+
+        if &ident[..4] != ELF_MAGIC {
+            return Err("File is not an ELF executable".into());
+        }
+
+        let is_64 = ident[4] == 2;
+        let little_endian = ident[5] == 1;
+        let h = Reader { little_endian };
+        let machine = h.u16(&ident[0x12..]);
+
+        let (shoff, shentsize, shnum, shstrndx) = if is_64 {
+            (
+                h.u64(&ident[0x28..]),
+                h.u16(&ident[0x3A..]),
+                h.u16(&ident[0x3C..]),
+                h.u16(&ident[0x3E..]),
+            )
+        } else {
+            (
+                h.u32(&ident[0x20..]) as u64,
+                h.u16(&ident[0x2E..]),
+                h.u16(&ident[0x30..]),
+                h.u16(&ident[0x32..]),
+            )
+        };
+
+        Ok(Self {
+            is_64,
+            little_endian,
+            machine,
+            shoff,
+            shentsize,
+            shnum,
+            shstrndx,
+        })
+    }
+
+    /// Size of the ELF runtime, which ends with the section header table
+    fn size(&self) -> u64 {
+        self.shoff + self.shentsize as u64 * self.shnum as u64
+    }
+
+    /// Reads the section headers, checking them against `len`, the size of the file
+    fn sections(&self, file: &mut (impl Read + Seek), len: u64) -> Result<Vec<Section>, String> {
+        let r = Reader {
+            little_endian: self.little_endian,
+        };
+        if self.shnum == 0 {
+            return Ok(Vec::new());
+        }
+        let expected = if self.is_64 {
+            SHENTSIZE_64
+        } else {
+            SHENTSIZE_32
+        };
+        if self.shentsize != expected {
+            return Err(format!(
+                "Invalid ELF section header size {:#x}",
+                self.shentsize
+            ));
+        }
+
+        let table = read_at(
+            file,
+            self.shoff,
+            self.shentsize as u64 * self.shnum as u64,
+            len,
+        )
+        .map_err(|_| "Section headers lie outside the file".to_string())?;
+
+        Ok(table
+            .chunks_exact(self.shentsize as usize)
+            .map(|entry| {
+                if self.is_64 {
+                    Section {
+                        name_offset: r.u32(entry),
+                        offset: r.u64(&entry[0x18..]),
+                        size: r.u64(&entry[0x20..]),
+                    }
+                } else {
+                    Section {
+                        name_offset: r.u32(entry),
+                        offset: r.u32(&entry[0x10..]) as u64,
+                        size: r.u32(&entry[0x14..]) as u64,
+                    }
+                }
+            })
+            .collect())
+    }
+}
+
+struct Reader {
+    little_endian: bool,
+}
+
+impl Reader {
+    fn u16(&self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        if self.little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        }
+    }
+
+    fn u32(&self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        }
+    }
+
+    fn u64(&self, b: &[u8]) -> u64 {
+        let b = [b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]];
+        if self.little_endian {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        }
+    }
+}
+
+/// Reads `size` bytes at `offset`, refusing ranges past `len`, the size of the file
+fn read_at(
+    file: &mut (impl Read + Seek),
+    offset: u64,
+    size: u64,
+    len: u64,
+) -> Result<Vec<u8>, String> {
+    if offset.checked_add(size).is_none_or(|end| end > len) {
+        return Err("Section lies outside the file".into());
+    }
+    let mut buf = vec![0u8; size as usize];
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_exact(&mut buf))
+        .map_err(|e| format!("Failed to read section: {}", e))?;
+    Ok(buf)
+}
+
+/// Returns the AppImage type (1 or 2) from the magic bytes at offset 8
+pub fn appimage_type(path: &Path) -> Option<u8> {
+    let mut header = [0u8; 11];
+    fs::File::open(path).ok()?.read_exact(&mut header).ok()?;
+    (&header[..4] == ELF_MAGIC && &header[8..10] == b"AI").then_some(header[10])
+}
+
+/// Returns the architecture the AppImage runtime was built for
+pub fn architecture(path: &Path) -> Result<&'static str, String> {
+    let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let header = ElfHeader::read(&mut file)?;
+    Ok(match header.machine {
+        0x03 => "i686",
+        0x28 => "armhf",
+        0x3E => "x86_64",
+        0xB7 => "aarch64",
+        0xF3 => "riscv64",
+        0x15 => "ppc64le",
+        0x16 => "s390x",
+        0x102 => "loongarch64",
+        _ => "unknown",
+    })
+}
+
+/// Finds an ELF section of the AppImage runtime by name and returns its offset and size
+pub fn section_range(path: &Path, name: &str) -> Result<Option<(u64, u64)>, String> {
+    let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    find_section(&mut file, len, name)
+}
+
+fn find_section(
+    file: &mut (impl Read + Seek),
+    len: u64,
+    name: &str,
+) -> Result<Option<(u64, u64)>, String> {
+    let header = ElfHeader::read(file)?;
+    let sections = header.sections(file, len)?;
+
+    let Some(strtab) = sections.get(header.shstrndx as usize) else {
+        return Ok(None);
+    };
+    let names = read_at(file, strtab.offset, strtab.size, len)?;
+
+    for section in &sections {
+        let start = section.name_offset as usize;
+        let Some(rest) = names.get(start..) else {
+            continue;
+        };
+        let end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        if &rest[..end] == name.as_bytes() {
+            if section
+                .offset
+                .checked_add(section.size)
+                .is_none_or(|e| e > len)
+            {
+                return Err(format!("Section {} lies outside the file", name));
+            }
+            return Ok(Some((section.offset, section.size)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Checks that a file is a type 2 AppImage built for `arch`, describing what it is otherwise
+///
+/// Type 1 AppImages (ISO 9660 images) are rejected: they have been deprecated for years, and
+/// neither embedded signatures nor extraction without running them work for them.
+pub fn validate(path: &Path, arch: &str) -> Result<(), String> {
+    let mut header = Vec::with_capacity(512);
+    fs::File::open(path)
+        .and_then(|f| f.take(512).read_to_end(&mut header))
+        .map_err(|e| e.to_string())?;
+
+    if !header.starts_with(ELF_MAGIC) {
+        let text = String::from_utf8_lossy(&header).trim_start().to_lowercase();
+        let kind = if text.starts_with("<!doctype html") || text.starts_with("<html") {
+            "an HTML page"
+        } else if header.starts_with(b"PK\x03\x04") {
+            "a zip archive"
+        } else if header.starts_with(b"\x1f\x8b") {
+            "a gzip archive"
+        } else if header.starts_with(b"#!") {
+            "a script"
+        } else {
+            "not an executable"
+        };
+        return Err(format!(
+            "The download is {}, not an AppImage. Check the URL.",
+            kind
+        ));
+    }
+
+    match appimage_type(path) {
+        Some(2) => {}
+        Some(1) => {
+            return Err(
+                "The download is a deprecated type 1 AppImage, only type 2 is supported.".into(),
+            );
+        }
+        Some(t) => return Err(format!("Unsupported AppImage type {}", t)),
+        None => {
+            return Err("The download is an ELF executable, but not an AppImage.".into());
+        }
+    }
+
+    let machine = architecture(path)?;
+    if machine != arch {
+        return Err(format!(
+            "The download is an AppImage for {}, but this installation needs {}. \
+             Use --arch {} if that is intended.",
+            machine, arch, machine
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reads the contents of an ELF section of the AppImage runtime by name
+pub fn read_section(path: &Path, name: &str) -> Result<Option<Vec<u8>>, String> {
+    let Some((offset, size)) = section_range(path, name)? else {
+        return Ok(None);
+    };
+    let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    read_at(&mut file, offset, size, len).map(Some)
+}
+
+/// Reads the embedded update information (e.g. `gh-releases-zsync|owner|repo|latest|*.zsync`)
+pub fn update_information(path: &Path) -> Option<String> {
+    let data = read_section(path, ".upd_info").ok()??;
+    let text = String::from_utf8_lossy(&data)
+        .trim_matches(char::from(0))
+        .trim()
+        .to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Reads the top-level desktop file from the SquashFS payload of a type 2 AppImage
+///
+/// The payload starts right after the ELF runtime and is parsed directly, so the AppImage is
+/// never run and does not need to be executable.
+pub fn desktop_file(path: &Path) -> Result<Option<String>, String> {
+    if appimage_type(path) != Some(2) {
+        return Ok(None);
+    }
+
+    let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let header = ElfHeader::read(&mut file)?;
+    let mut image = squashfs::Image::open(file, header.size(), len)?;
+    Ok(image
+        .find_root_file(|name| name.ends_with(".desktop"))?
+        .map(|(_, data)| String::from_utf8_lossy(&data).into_owned()))
+}
+
+/// Looks up a key in the `[Desktop Entry]` group of a desktop file
+pub fn desktop_value(content: &str, key: &str) -> Option<String> {
+    let mut in_entry = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry {
+            continue;
+        }
+        if let Some((k, v)) = line.split_once('=')
+            && k.trim() == key
+        {
+            return Some(v.trim().to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const NAMES: &[u8] = b"\0.shstrtab\0.upd_info\0";
+    const DATA: &[u8] = b"zsync|https://example.com/App.AppImage.zsync";
+    const NAMES_AT: u64 = 64;
+    const DATA_AT: u64 = NAMES_AT + NAMES.len() as u64;
+    const SHOFF: u64 = DATA_AT + DATA.len() as u64;
+
+    /// A 64-bit ELF with a null section, `.shstrtab` and `.upd_info`
+    fn elf() -> Vec<u8> {
+        let mut file = vec![0u8; 64];
+        file[..4].copy_from_slice(ELF_MAGIC);
+        file[4] = 2;
+        file[5] = 1;
+        file[0x12..0x14].copy_from_slice(&0x3Eu16.to_le_bytes());
+        file[0x28..0x30].copy_from_slice(&SHOFF.to_le_bytes());
+        set_shentsize(&mut file, SHENTSIZE_64);
+        set_shnum(&mut file, 3);
+        file[0x3E..0x40].copy_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(NAMES);
+        file.extend_from_slice(DATA);
+
+        let sections = [
+            (0u32, 0, 0),
+            (1, NAMES_AT, NAMES.len() as u64),
+            (11, DATA_AT, DATA.len() as u64),
+        ];
+        for (name, offset, size) in sections {
+            let mut entry = [0u8; SHENTSIZE_64 as usize];
+            entry[..4].copy_from_slice(&name.to_le_bytes());
+            entry[0x18..0x20].copy_from_slice(&offset.to_le_bytes());
+            entry[0x20..0x28].copy_from_slice(&size.to_le_bytes());
+            file.extend_from_slice(&entry);
+        }
+        file
+    }
+
+    fn set_shentsize(file: &mut [u8], size: u16) {
+        file[0x3A..0x3C].copy_from_slice(&size.to_le_bytes());
+    }
+
+    fn set_shnum(file: &mut [u8], count: u16) {
+        file[0x3C..0x3E].copy_from_slice(&count.to_le_bytes());
+    }
+
+    fn find(file: &[u8], name: &str) -> Result<Option<(u64, u64)>, String> {
+        find_section(&mut Cursor::new(file), file.len() as u64, name)
+    }
+
+    #[test]
+    fn finds_sections_by_name() {
+        let file = elf();
+        assert_eq!(
+            find(&file, ".upd_info"),
+            Ok(Some((DATA_AT, DATA.len() as u64)))
+        );
+        assert_eq!(find(&file, ".sha256_sig"), Ok(None));
+    }
+
+    #[test]
+    fn no_section_headers() {
+        let mut file = elf();
+        set_shnum(&mut file, 0);
+        assert_eq!(find(&file, ".upd_info"), Ok(None));
+    }
+
+    #[test]
+    fn rejects_zero_entry_size() {
+        let mut file = elf();
+        set_shentsize(&mut file, 0);
+        assert!(find(&file, ".upd_info").is_err());
+    }
+
+    #[test]
+    fn rejects_short_entry_size() {
+        let mut file = elf();
+        set_shentsize(&mut file, 0x10);
+        assert!(find(&file, ".upd_info").is_err());
+        set_shentsize(&mut file, SHENTSIZE_32);
+        assert!(find(&file, ".upd_info").is_err());
+    }
+
+    #[test]
+    fn rejects_section_table_past_the_end() {
+        let mut file = elf();
+        set_shnum(&mut file, u16::MAX);
+        assert!(find(&file, ".upd_info").is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let file = elf();
+        assert!(find(&file[..40], ".upd_info").is_err());
+        assert!(find(&file[..file.len() - 8], ".upd_info").is_err());
+    }
+
+    #[test]
+    fn rejects_sections_past_the_end() {
+        let mut file = elf();
+        let size_at = SHOFF as usize + 2 * SHENTSIZE_64 as usize + 0x20;
+        file[size_at..size_at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(find(&file, ".upd_info").is_err());
+    }
+}
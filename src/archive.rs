@@ -0,0 +1,284 @@
+use crate::host;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    TarGz,
+    Zip,
+}
+
+/// Archive format of a release asset, judged by its file name
+pub fn kind(name: &str) -> Option<Kind> {
+    let name = name.to_lowercase();
+    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(Kind::TarGz)
+    } else if name.ends_with(".zip") {
+        Some(Kind::Zip)
+    } else {
+        None
+    }
+}
+
+/// Archive format of a downloaded file, judged by its magic bytes
+pub fn detect(path: &Path) -> Option<Kind> {
+    let mut magic = [0u8; 4];
+    fs::File::open(path).ok()?.read_exact(&mut magic).ok()?;
+    match magic {
+        [0x1f, 0x8b, ..] => Some(Kind::TarGz),
+        [b'P', b'K', 3, 4] => Some(Kind::Zip),
+        _ => None,
+    }
+}
+
+/// Extracts an AppImage from the archive to `dest` and returns its path inside the archive
+///
+/// Without `inner_path` the AppImage is picked by name, preferring one for `arch`.
+pub fn extract_appimage(
+    archive: &Path,
+    kind: Kind,
+    inner_path: Option<&str>,
+    arch: &str,
+    dest: &Path,
+) -> Result<String, String> {
+    let inner_path = match inner_path {
+        Some(path) => path.to_string(),
+        None => {
+            find_appimage(archive, kind, arch)?.ok_or("The archive does not contain an AppImage")?
+        }
+    };
+    let not_a_file = || format!("'{}' in the archive is not a regular file", inner_path);
+
+    let mut out = fs::File::create(dest).map_err(|e| format!("Failed to create file: {}", e))?;
+    let found = match kind {
+        Kind::TarGz => {
+            let mut tar = tar_archive(archive)?;
+            let mut found = false;
+            for entry in tar.entries().map_err(|e| e.to_string())? {
+                let mut entry = entry.map_err(|e| e.to_string())?;
+                let path = entry.path().map_err(|e| e.to_string())?;
+                if path.to_string_lossy() == inner_path {
+                    // Links would point the copy at other files on this machine
+                    if !entry.header().entry_type().is_file() {
+                        return Err(not_a_file());
+                    }
+                    io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+                    found = true;
+                    break;
+                }
+            }
+            found
+        }
+        Kind::Zip => {
+            let mut zip = zip_archive(archive)?;
+            match zip.by_name(&inner_path) {
+                Ok(mut entry) => {
+                    if !entry.is_file() {
+                        return Err(not_a_file());
+                    }
+                    io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+    };
+
+    if !found {
+        return Err(format!("'{}' was not found in the archive", inner_path));
+    }
+    Ok(inner_path)
+}
+
+/// Paths of the regular files in the archive
+fn list(archive: &Path, kind: Kind) -> Result<Vec<String>, String> {
+    match kind {
+        Kind::TarGz => {
+            let mut tar = tar_archive(archive)?;
+            let mut files = Vec::new();
+            for entry in tar.entries().map_err(|e| e.to_string())? {
+                let entry = entry.map_err(|e| e.to_string())?;
+                if entry.header().entry_type().is_file() {
+                    let path = entry.path().map_err(|e| e.to_string())?;
+                    files.push(path.to_string_lossy().to_string());
+                }
+            }
+            Ok(files)
+        }
+        Kind::Zip => {
+            let mut zip = zip_archive(archive)?;
+            let mut files = Vec::new();
+            for i in 0..zip.len() {
+                let entry = zip.by_index_raw(i).map_err(|e| e.to_string())?;
+                if entry.is_file() {
+                    files.push(entry.name().to_string());
+                }
+            }
+            Ok(files)
+        }
+    }
+}
+
+/// Path of the AppImage `extract_appimage` would pick from the archive, if it holds one
+pub fn find_appimage(archive: &Path, kind: Kind, arch: &str) -> Result<Option<String>, String> {
+    Ok(pick_appimage(list(archive, kind)?, arch))
+}
+
+fn pick_appimage(files: Vec<String>, arch: &str) -> Option<String> {
+    let appimages: Vec<String> = files
+        .into_iter()
+        .filter(|f| f.to_lowercase().ends_with(".appimage"))
+        .collect();
+
+    host::arch_aliases(arch)
+        .iter()
+        .find_map(|alias| appimages.iter().find(|f| f.to_lowercase().contains(alias)))
+        .or(appimages.first())
+        .cloned()
+}
+
+/// Packs directories into a gzipped tarball at `dest`, each under its own name
+pub fn create_tar_gz(dest: &Path, dirs: &[PathBuf]) -> Result<(), String> {
+    let file = fs::File::create(dest).map_err(|e| format!("Failed to create {:?}: {}", dest, e))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    for dir in dirs {
+        let name = dir
+            .file_name()
+            .ok_or_else(|| format!("{:?} has no file name", dir))?;
+        builder
+            .append_dir_all(name, dir)
+            .map_err(|e| format!("Failed to archive {:?}: {}", dir, e))?;
+    }
+    builder
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .map_err(|e| format!("Failed to write {:?}: {}", dest, e))?;
+    Ok(())
+}
+
+fn tar_archive(path: &Path) -> Result<tar::Archive<GzDecoder<fs::File>>, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+fn zip_archive(path: &Path) -> Result<zip::ZipArchive<impl Read + io::Seek>, String> {
+    let file = fs::File::open(path).map_err(|e| e.to_string())?;
+    zip::ZipArchive::new(file).map_err(|e| format!("Invalid zip archive: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const APPIMAGE: &[u8] = b"\x7fELF appimage";
+
+    fn tar_gz(dir: &Path, files: &[&str], links: &[(&str, &str)]) -> PathBuf {
+        let path = dir.join("test.tar.gz");
+        let file = fs::File::create(&path).unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        for name in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(APPIMAGE.len() as u64);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, name, APPIMAGE).unwrap();
+        }
+        for (name, target) in links {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, name, target).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        path
+    }
+
+    fn zip(dir: &Path, files: &[&str]) -> PathBuf {
+        let path = dir.join("test.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("dist/", options).unwrap();
+        for name in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(APPIMAGE).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn detects_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let tar = tar_gz(dir.path(), &["App.AppImage"], &[]);
+        let zip = zip(dir.path(), &["App.AppImage"]);
+        let other = dir.path().join("App.AppImage");
+        fs::write(&other, APPIMAGE).unwrap();
+
+        assert!(matches!(detect(&tar), Some(Kind::TarGz)));
+        assert!(matches!(detect(&zip), Some(Kind::Zip)));
+        assert!(detect(&other).is_none());
+        assert!(matches!(kind("App-1.0.TGZ"), Some(Kind::TarGz)));
+        assert!(kind("App.AppImage").is_none());
+    }
+
+    #[test]
+    fn picks_the_appimage_for_the_architecture() {
+        let files = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+        let both = files(&["App-x86_64.AppImage", "App-aarch64.AppImage", "README"]);
+        assert_eq!(
+            pick_appimage(both, "aarch64").as_deref(),
+            Some("App-aarch64.AppImage")
+        );
+        let other = files(&["App-x86_64.AppImage"]);
+        assert_eq!(
+            pick_appimage(other, "aarch64").as_deref(),
+            Some("App-x86_64.AppImage")
+        );
+        assert_eq!(pick_appimage(files(&["app", "README"]), "x86_64"), None);
+    }
+
+    #[test]
+    fn extracts_appimages() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out");
+        for (archive, kind) in [
+            (tar_gz(dir.path(), &["dist/App.AppImage"], &[]), Kind::TarGz),
+            (zip(dir.path(), &["dist/App.AppImage"]), Kind::Zip),
+        ] {
+            let inner = extract_appimage(&archive, kind, None, "x86_64", &dest).unwrap();
+            assert_eq!(inner, "dist/App.AppImage");
+            assert_eq!(fs::read(&dest).unwrap(), APPIMAGE);
+
+            let inner = extract_appimage(&archive, kind, Some(&inner), "x86_64", &dest);
+            assert_eq!(inner.as_deref(), Ok("dist/App.AppImage"));
+            assert!(extract_appimage(&archive, kind, Some("App"), "x86_64", &dest).is_err());
+        }
+    }
+
+    #[test]
+    fn archives_without_appimages() {
+        let dir = tempfile::tempdir().unwrap();
+        let tar = tar_gz(dir.path(), &["bin/app", "README"], &[]);
+        assert_eq!(find_appimage(&tar, Kind::TarGz, "x86_64"), Ok(None));
+        assert!(
+            extract_appimage(&tar, Kind::TarGz, None, "x86_64", &dir.path().join("out")).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let tar = tar_gz(dir.path(), &[], &[("App.AppImage", "/etc/passwd")]);
+        // Links are not offered as candidates, nor extracted when named explicitly
+        assert_eq!(find_appimage(&tar, Kind::TarGz, "x86_64"), Ok(None));
+        let dest = dir.path().join("out");
+        assert!(
+            extract_appimage(&tar, Kind::TarGz, Some("App.AppImage"), "x86_64", &dest).is_err()
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{config::AxePaths, download};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Size limit of the download cache when the config sets none
+pub const DEFAULT_LIMIT_MB: u64 = 2048;
+
+/// An AppImage in the cache, stored under its SHA-256
+pub struct Entry {
+    pub hash: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// Last time the entry was stored or restored
+    pub used: SystemTime,
+}
+
+/// Size limit in bytes from the config
+pub fn limit(paths: &AxePaths) -> u64 {
+    let limit_mb = paths
+        .load_config()
+        .ok()
+        .and_then(|c| c.cache_limit_mb)
+        .unwrap_or(DEFAULT_LIMIT_MB);
+    limit_mb * 1024 * 1024
+}
+
+/// Cached AppImages, least recently used first
+pub fn entries(paths: &AxePaths) -> Vec<Entry> {
+    let Ok(dir) = fs::read_dir(paths.cache_dir()) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<Entry> = dir
+        .flatten()
+        .filter_map(|e| {
+            // Skips copies still being written, which carry a `.part` extension
+            let hash = e.file_name().to_string_lossy().to_string();
+            if !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let metadata = e.metadata().ok().filter(|m| m.is_file())?;
+            Some(Entry {
+                hash,
+                path: e.path(),
+                size: metadata.len(),
+                used: metadata.modified().ok()?,
+            })
+        })
+        .collect();
+    entries.sort_by_key(|e| e.used);
+    entries
+}
+
+/// Keeps a copy of an installed AppImage, then trims the cache to its size limit
+pub fn store(paths: &AxePaths, file: &Path, hash: &str) -> Result<(), String> {
+    let limit = limit(paths);
+    if limit == 0 || hash.is_empty() {
+        return Ok(());
+    }
+
+    let cached = paths.cache_dir().join(hash);
+    if cached.exists() {
+        touch(&cached);
+    } else {
+        fs::create_dir_all(paths.cache_dir()).map_err(|e| e.to_string())?;
+        let part = cached.with_extension("part");
+        fs::copy(file, &part).map_err(|e| e.to_string())?;
+        fs::rename(&part, &cached).map_err(|e| e.to_string())?;
+    }
+
+    trim(entries(paths), limit, &HashSet::from([hash]));
+    Ok(())
+}
+
+/// Restores the AppImage with this hash to `dest`, returning false when it isn't cached
+///
+/// A cached file that no longer matches its hash is removed.
+pub fn restore(paths: &AxePaths, hash: &str, dest: &Path) -> Result<bool, String> {
+    let cached = paths.cache_dir().join(hash);
+    if hash.is_empty() || !cached.is_file() {
+        return Ok(false);
+    }
+    if download::calculate_hash(&cached)? != hash {
+        let _ = fs::remove_file(&cached);
+        return Ok(false);
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let mut part = dest.as_os_str().to_os_string();
+    part.push(".part");
+    let part = PathBuf::from(part);
+    fs::copy(&cached, &part).map_err(|e| format!("Failed to restore from cache: {}", e))?;
+    download::set_executable(&part)?;
+    fs::rename(&part, dest).map_err(|e| format!("Failed to restore from cache: {}", e))?;
+    touch(&cached);
+    Ok(true)
+}
+
+/// Removes cached AppImages not in `keep`, then the least recently used ones over `limit`
+///
+/// Returns the number of removed files and the bytes freed.
+pub fn prune(paths: &AxePaths, keep: &HashSet<&str>, limit: u64) -> (usize, u64) {
+    let (unused, kept): (Vec<Entry>, Vec<Entry>) = entries(paths)
+        .into_iter()
+        .partition(|e| !keep.contains(e.hash.as_str()));
+
+    let (mut removed, mut freed) = (0, 0);
+    for entry in unused {
+        if fs::remove_file(&entry.path).is_ok() {
+            removed += 1;
+            freed += entry.size;
+        }
+    }
+    let (trimmed, trimmed_size) = trim(kept, limit, &HashSet::new());
+    (removed + trimmed, freed + trimmed_size)
+}
+
+/// Removes the oldest entries until the cache fits in `limit`, sparing `keep`
+fn trim(entries: Vec<Entry>, limit: u64, keep: &HashSet<&str>) -> (usize, u64) {
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    let (mut removed, mut freed) = (0, 0);
+    for entry in entries {
+        if total <= limit {
+            break;
+        }
+        if keep.contains(entry.hash.as_str()) {
+            continue;
+        }
+        if fs::remove_file(&entry.path).is_ok() {
+            total -= entry.size;
+            removed += 1;
+            freed += entry.size;
+        }
+    }
+    (removed, freed)
+}
+
+/// Marks an entry as recently used
+fn touch(path: &Path) {
+    if let Ok(file) = fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn paths(dir: &Path) -> AxePaths {
+        AxePaths {
+            config_dir: dir.join("config"),
+            data_dir: dir.to_path_buf(),
+            bin_dir: dir.join("bin"),
+            applications_dir: dir.join("applications"),
+            link_dir: None,
+            system: false,
+        }
+    }
+
+    /// Caches `size` bytes under `hash`, last used `age` seconds ago
+    fn cache(paths: &AxePaths, hash: &str, size: usize, age: u64) {
+        fs::create_dir_all(paths.cache_dir()).unwrap();
+        let path = paths.cache_dir().join(hash);
+        fs::write(&path, vec![0u8; size]).unwrap();
+        let file = fs::File::options().append(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(age))
+            .unwrap();
+    }
+
+    fn cached(paths: &AxePaths) -> Vec<String> {
+        entries(paths).into_iter().map(|e| e.hash).collect()
+    }
+
+    #[test]
+    fn lists_entries_least_recently_used_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+        cache(&paths, "bb", 1, 10);
+        cache(&paths, "aa", 1, 30);
+        cache(&paths, "cc", 1, 20);
+        fs::write(paths.cache_dir().join("dd.part"), b"x").unwrap();
+        assert_eq!(cached(&paths), ["aa", "cc", "bb"]);
+    }
+
+    #[test]
+    fn trims_the_oldest_entries_over_the_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+        cache(&paths, "aa", 100, 30);
+        cache(&paths, "bb", 100, 20);
+        cache(&paths, "cc", 100, 10);
+
+        assert_eq!(trim(entries(&paths), 300, &HashSet::new()), (0, 0));
+        assert_eq!(trim(entries(&paths), 200, &HashSet::from(["aa"])), (1, 100));
+        assert_eq!(cached(&paths), ["aa", "cc"]);
+        assert_eq!(trim(entries(&paths), 0, &HashSet::new()), (2, 200));
+        assert!(cached(&paths).is_empty());
+    }
+
+    #[test]
+    fn prunes_unused_entries_then_trims() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+        cache(&paths, "aa", 100, 30);
+        cache(&paths, "bb", 100, 20);
+        cache(&paths, "cc", 100, 10);
+        cache(&paths, "dd", 100, 5);
+
+        let keep = HashSet::from(["aa", "bb", "cc"]);
+        assert_eq!(prune(&paths, &keep, 150), (3, 300));
+        assert_eq!(cached(&paths), ["cc"]);
+
+        assert_eq!(prune(&paths, &HashSet::new(), u64::MAX), (1, 100));
+        assert!(cached(&paths).is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{config::AxePaths, host};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+const FEED_URL: &str = "https://appimage.github.io/feed.json";
+
+/// How long the cached feed is used before it is downloaded again
+const CACHE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize, Deserialize)]
+pub struct Feed {
+    pub items: Vec<FeedItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FeedItem {
+    pub name: String,
+    pub description: Option<String>,
+    pub links: Option<Vec<FeedLink>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FeedLink {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub url: String,
+}
+
+impl FeedItem {
+    /// Source usable with `axe add`: `owner/repo` for GitHub projects, or a direct AppImage URL
+    pub fn source(&self) -> Option<String> {
+        let links = self.links.as_deref().unwrap_or_default();
+        links
+            .iter()
+            .filter(|l| l.kind == "GitHub")
+            .find_map(|l| {
+                // Links may point into the repo, e.g. to its releases
+                let path = l
+                    .url
+                    .strip_prefix("https://github.com/")
+                    .or_else(|| l.url.strip_prefix("http://github.com/"))?;
+                let mut parts = path.split('/').filter(|p| !p.is_empty());
+                Some(format!("{}/{}", parts.next()?, parts.next()?))
+            })
+            .or_else(|| {
+                links
+                    .iter()
+                    .find(|l| l.kind == "Download" && l.url.to_lowercase().ends_with(".appimage"))
+                    .map(|l| l.url.clone())
+            })
+    }
+
+    pub fn matches(&self, term: &str) -> bool {
+        let term = term.to_lowercase();
+        self.name.to_lowercase().contains(&term)
+            || self
+                .description
+                .as_deref()
+                .is_some_and(|d| d.to_lowercase().contains(&term))
+    }
+}
+
+/// Looks up an app by name and returns its catalog id and underlying source
+pub async fn resolve(paths: &AxePaths, name: &str) -> Result<(String, String), String> {
+    let feed = load_feed(paths, false, false).await?;
+    let item = feed
+        .items
+        .iter()
+        .find(|item| item.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            format!(
+                "'{}' is not in the AppImageHub catalog. Try 'axe search {}'.",
+                name, name
+            )
+        })?;
+    let source = item.source().ok_or_else(|| {
+        format!(
+            "The catalog entry for '{}' has no GitHub repository or AppImage download link.",
+            item.name
+        )
+    })?;
+    Ok((item.name.clone(), source))
+}
+
+/// Loads the AppImageHub feed, downloading it when the cached copy is stale
+///
+/// With `offline` only the cached copy is used.
+pub async fn load_feed(paths: &AxePaths, offline: bool, refresh: bool) -> Result<Feed, String> {
+    let cache = paths.catalog_cache_path();
+    let age = fs::metadata(&cache)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok());
+
+    let fresh = age.is_some_and(|a| a < CACHE_MAX_AGE);
+    if offline || (fresh && !refresh) {
+        return read_cache(paths);
+    }
+
+    match download_feed().await {
+        Ok(content) => {
+            let feed = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse catalog: {}", e))?;
+            // Users can search the system catalog, but only root can refresh its cache
+            if paths.system && !host::is_root() {
+                return Ok(feed);
+            }
+            if let Some(parent) = cache.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            if let Err(e) = fs::write(&cache, content) {
+                eprintln!("Warning: Failed to cache catalog: {}", e);
+            }
+            Ok(feed)
+        }
+        Err(e) if age.is_some() => {
+            eprintln!("Warning: {}. Using the cached catalog.", e);
+            read_cache(paths)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn read_cache(paths: &AxePaths) -> Result<Feed, String> {
+    let content = fs::read_to_string(paths.catalog_cache_path()).map_err(|_| {
+        "No cached catalog available. Run the search once while online.".to_string()
+    })?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse cached catalog: {}", e))
+}
+
+async fn download_feed() -> Result<String, String> {
+    let response = reqwest::get(FEED_URL)
+        .await
+        .map_err(|e| format!("Failed to download catalog: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download catalog: server returned {}",
+            response.status()
+        ));
+    }
+
+    response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to download catalog: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn item(name: &str, description: Option<&str>, links: &[(&str, &str)]) -> FeedItem {
+        FeedItem {
+            name: name.into(),
+            description: description.map(String::from),
+            links: Some(
+                links
+                    .iter()
+                    .map(|(kind, url)| FeedLink {
+                        kind: kind.to_string(),
+                        url: url.to_string(),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn sources_from_links() {
+        let github = item("App", None, &[("GitHub", "https://github.com/acme/app/")]);
+        assert_eq!(github.source().as_deref(), Some("acme/app"));
+
+        let releases = item(
+            "App",
+            None,
+            &[("GitHub", "https://github.com/acme/app/releases")],
+        );
+        assert_eq!(releases.source().as_deref(), Some("acme/app"));
+
+        let download = item(
+            "App",
+            None,
+            &[
+                ("Download", "https://example.com/App.zip"),
+                ("Download", "https://example.com/App.AppImage"),
+            ],
+        );
+        assert_eq!(
+            download.source().as_deref(),
+            Some("https://example.com/App.AppImage")
+        );
+
+        let elsewhere = item("App", None, &[("GitHub", "https://gitlab.com/acme/app")]);
+        assert_eq!(elsewhere.source(), None);
+        assert_eq!(
+            item("App", None, &[("GitHub", "https://github.com/acme")]).source(),
+            None
+        );
+        assert_eq!(
+            FeedItem {
+                links: None,
+                ..item("App", None, &[])
+            }
+            .source(),
+            None
+        );
+    }
+
+    #[test]
+    fn matches_names_and_descriptions() {
+        let app = item("Krita", Some("Digital PAINTING studio"), &[]);
+        assert!(app.matches("krit"));
+        assert!(app.matches("KRITA"));
+        assert!(app.matches("painting"));
+        assert!(!app.matches("gimp"));
+        assert!(!item("Krita", None, &[]).matches("painting"));
+    }
+
+    fn paths(dir: &Path) -> AxePaths {
+        AxePaths {
+            config_dir: dir.join("config"),
+            data_dir: dir.join("data"),
+            bin_dir: dir.join("data/bin"),
+            applications_dir: dir.join("applications"),
+            link_dir: None,
+            system: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_apps_from_the_cached_feed() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+        let feed = Feed {
+            items: vec![
+                item("Krita", None, &[("GitHub", "https://github.com/KDE/krita")]),
+                item("NoLinks", None, &[]),
+            ],
+        };
+        let cache = paths.catalog_cache_path();
+        fs::create_dir_all(cache.parent().unwrap()).unwrap();
+        fs::write(&cache, serde_json::to_string(&feed).unwrap()).unwrap();
+
+        assert_eq!(
+            resolve(&paths, "krita").await,
+            Ok(("Krita".to_string(), "KDE/krita".to_string()))
+        );
+        assert!(resolve(&paths, "NoLinks").await.is_err());
+        assert!(resolve(&paths, "Gimp").await.is_err());
+    }
+}
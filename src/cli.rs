@@ -1,5 +1,13 @@
+use crate::{
+    config::{self, HomeAccess, RunMode, SandboxBackend},
+    host,
+    output::OutputFormat,
+};
 use clap::{Args, Parser, Subcommand};
-use std::str::FromStr;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 #[derive(Parser, Debug)]
 #[command(name = "axe",
@@ -9,6 +17,31 @@ use std::str::FromStr;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Manage the system-wide installation in /opt/axe (requires root)
+    #[arg(long, global = true)]
+    pub system: bool,
+
+    /// Output format of list, verify, outdated and info
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Architecture to install AppImages for [default: this machine's]
+    #[arg(long, global = true, value_parser = parse_arch)]
+    pub arch: Option<String>,
+    /// Never use the network: install from the download cache and fail instead of connecting
+    #[arg(long, global = true)]
+    pub offline: bool,
+}
+
+fn parse_arch(input: &str) -> Result<String, String> {
+    host::normalize_arch(input)
+        .map(String::from)
+        .ok_or_else(|| {
+            "unknown architecture, use one of x86_64, aarch64, armhf, i686, riscv64, \
+             ppc64le, s390x or loongarch64"
+                .to_string()
+        })
 }
 
 #[derive(Subcommand, Debug)]
@@ -33,6 +66,286 @@ pub enum Commands {
 
     /// Remove a package and its desktop entry
     Remove(RemoveArgs),
+
+    /// Import AppImages that were downloaded by hand
+    Adopt(AdoptArgs),
+
+    /// Delete binaries and desktop entries that no package refers to
+    Clean(CleanArgs),
+
+    /// Diagnose common reasons AppImages fail to start
+    Doctor,
+
+    /// Choose how a package is started (e.g. without FUSE)
+    SetRunMode(SetRunModeArgs),
+
+    /// Confine a package with bubblewrap or firejail
+    Sandbox(SandboxArgs),
+
+    /// Show or set environment variables for a package
+    SetEnv(SetEnvArgs),
+
+    /// Show or set default arguments and working directory for a package
+    SetArgs(SetArgsArgs),
+
+    /// Enable or disable portable home and config dirs for a package
+    SetPortable(SetPortableArgs),
+
+    /// Show, back up or wipe a package's portable data
+    Data(DataArgs),
+
+    /// Check installed binaries against the hashes in the lockfile
+    Verify,
+
+    /// List packages with available updates without installing them
+    ///
+    /// Exits with code 100 when updates are available.
+    Outdated,
+
+    /// Show details about a package
+    Info(InfoArgs),
+
+    /// Search the AppImageHub catalog and GitHub for AppImages
+    Search(SearchArgs),
+
+    /// Manage package indexes that provide packages by name
+    Repo(RepoArgs),
+
+    /// Manage the cache of downloaded AppImages
+    Cache(CacheArgs),
+}
+
+impl Commands {
+    /// Whether the command can modify installed packages or the lockfile
+    pub fn modifies(&self) -> bool {
+        match self {
+            // Running a package that is missing downloads it, handle_run checks that itself
+            Commands::List
+            | Commands::Run(_)
+            | Commands::Doctor
+            | Commands::Verify
+            | Commands::Outdated
+            | Commands::Info(_) => false,
+            // Only adding the chosen result needs privileges, handle_search checks that itself
+            Commands::Search(_) => false,
+            Commands::Data(a) => a.wipe,
+            Commands::Repo(a) => !matches!(a.command, RepoCommands::List),
+            Commands::Cache(a) => !matches!(a.command, CacheCommands::Info),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AdoptArgs {
+    /// Directory to scan for AppImages [default: ~/Applications]
+    pub dir: Option<PathBuf>,
+
+    /// Symlink the AppImages into axe's bin dir instead of moving them
+    #[arg(short, long)]
+    pub link: bool,
+
+    /// Create desktop entries for the adopted packages
+    #[arg(short, long)]
+    pub desktop: bool,
+
+    /// Auto-agree to all prompts, skipping AppImages with an unknown source
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CleanArgs {
+    /// Only list the files that would be deleted
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Auto-agree to all prompts
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SetRunModeArgs {
+    /// Name of the package
+    pub name: String,
+
+    /// How the package should be started
+    #[arg(value_enum)]
+    pub mode: RunMode,
+}
+
+#[derive(Args, Debug)]
+pub struct SandboxArgs {
+    /// Name of the package
+    pub name: String,
+
+    /// Remove the sandbox profile and run the package unconfined
+    #[arg(long)]
+    pub disable: bool,
+
+    /// Sandbox tool to launch the package with
+    #[arg(long, value_enum, default_value_t = SandboxBackend::Auto)]
+    pub backend: SandboxBackend,
+
+    /// Cut off network access
+    #[arg(long)]
+    pub no_network: bool,
+
+    /// Access to the home directory
+    #[arg(long, value_enum, default_value_t = HomeAccess::None)]
+    pub home: HomeAccess,
+
+    /// Expose a path read-write inside the sandbox (can be repeated)
+    #[arg(long = "allow", value_name = "PATH")]
+    pub paths: Vec<PathBuf>,
+
+    /// Allow access to the GPU and other devices
+    #[arg(long)]
+    pub devices: bool,
+
+    /// Hide the X11 and Wayland sockets
+    #[arg(long)]
+    pub no_display: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SetEnvArgs {
+    /// Name of the package
+    pub name: String,
+
+    /// Variables to set, e.g. ELECTRON_OZONE_PLATFORM_HINT=wayland
+    #[arg(value_name = "KEY=VALUE")]
+    pub vars: Vec<String>,
+
+    /// Remove a variable (can be repeated)
+    #[arg(long, value_name = "KEY")]
+    pub unset: Vec<String>,
+
+    /// Remove all variables before setting new ones
+    #[arg(long)]
+    pub clear: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SetArgsArgs {
+    /// Name of the package
+    pub name: String,
+
+    /// Arguments passed on every launch (put them after '--')
+    #[arg(last = true)]
+    pub args: Vec<String>,
+
+    /// Directory to start the package in
+    #[arg(long, value_name = "DIR", conflicts_with = "no_cwd")]
+    pub cwd: Option<PathBuf>,
+
+    /// Start the package in the caller's directory again
+    #[arg(long)]
+    pub no_cwd: bool,
+
+    /// Remove all default arguments
+    #[arg(long, conflicts_with = "args")]
+    pub clear: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SetPortableArgs {
+    /// Name of the package
+    pub name: String,
+
+    /// Disable portable mode (the data dirs must be empty)
+    #[arg(long)]
+    pub off: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct DataArgs {
+    /// Name of the package
+    pub name: String,
+
+    /// Write a .tar.gz archive of the portable data to this file
+    #[arg(long, value_name = "FILE", conflicts_with = "wipe")]
+    pub backup: Option<PathBuf>,
+
+    /// Delete all portable data of the package
+    #[arg(long)]
+    pub wipe: bool,
+
+    /// Auto-agree to all prompts
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct InfoArgs {
+    /// Name of the package
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    /// Text to look for in app names and descriptions
+    pub term: String,
+
+    /// Also search GitHub repositories with AppImage releases and show the latest versions
+    #[arg(short, long)]
+    pub github: bool,
+
+    /// Download the catalog even if the cached copy is recent
+    #[arg(long)]
+    pub refresh: bool,
+
+    /// Maximum number of results to show
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub limit: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommands {
+    /// Show the size of the download cache
+    Info,
+    /// Remove cached AppImages no package uses and trim the cache to its size limit
+    Prune {
+        /// Remove every cached AppImage
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct RepoArgs {
+    #[command(subcommand)]
+    pub command: RepoCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RepoCommands {
+    /// Add a package index from a URL or local path
+    Add {
+        /// Name of the index
+        name: String,
+        /// URL or path of the TOML or JSON index file
+        url: String,
+    },
+
+    /// List configured package indexes and their packages
+    List,
+
+    /// Remove a package index
+    Remove {
+        /// Name of the index
+        name: String,
+    },
+
+    /// Download the latest version of all package indexes
+    Update,
 }
 
 #[derive(Args, Debug)]
@@ -43,6 +356,10 @@ pub struct RemoveArgs {
     /// Auto-agree to all prompts
     #[arg(short, long)]
     pub yes: bool,
+
+    /// Also delete the package's portable data without asking
+    #[arg(long)]
+    pub purge: bool,
 }
 
 #[derive(Args, Debug)]
@@ -76,8 +393,17 @@ pub struct RunArgs {
 
 #[derive(Args, Debug)]
 pub struct AddArgs {
-    /// Source to add from (GitHub repo 'owner/repo' or a URL)
-    pub source: Source,
+    /// Source to add from (GitHub repo 'owner/repo', a URL, a local path or file:// URL, 'catalog:<AppName>' or a package name from an index)
+    #[arg(required_unless_present = "page")]
+    pub source: Option<Source>,
+
+    /// Download page to scrape for the AppImage link instead of a source
+    #[arg(long, conflicts_with = "source", requires = "pattern")]
+    pub page: Option<String>,
+
+    /// Regex the link on the page must match, its first or 'version' capture group is the version
+    #[arg(long, requires = "page")]
+    pub pattern: Option<String>,
 
     /// Optional override for package name
     #[arg(long)]
@@ -87,6 +413,15 @@ pub struct AddArgs {
     #[arg(long)]
     pub prerelease: bool,
 
+    /// Install the artifact of the latest successful workflow run on this branch (for GitHub
+    /// sources)
+    #[arg(long, conflicts_with = "prerelease")]
+    pub branch: Option<String>,
+
+    /// Only consider runs of this workflow file, e.g. 'nightly.yml'
+    #[arg(long, requires = "branch")]
+    pub workflow: Option<String>,
+
     /// Auto-agree to all prompts
     #[arg(short, long)]
     pub yes: bool,
@@ -94,18 +429,70 @@ pub struct AddArgs {
     /// Create a desktop entry for the package
     #[arg(short, long)]
     pub desktop: bool,
+
+    /// Keep the app's home and config dirs next to the binary
+    #[arg(short, long)]
+    pub portable: bool,
 }
 
 #[derive(Debug, Clone)]
 pub enum Source {
-    Github { owner: String, repo: String },
+    Github {
+        owner: String,
+        repo: String,
+    },
     Url(String),
+    /// App name in the AppImageHub catalog
+    Catalog(String),
+    /// Package name looked up in the configured package indexes
+    Index(String),
+    /// AppImage on the local filesystem or a mounted share
+    Local(PathBuf),
+    /// Newest link matching `pattern` on a download page, from `--page`
+    Page {
+        url: String,
+        pattern: String,
+    },
+    /// Artifact of the latest workflow run on a branch, from `--branch`
+    Artifact {
+        owner: String,
+        repo: String,
+        branch: String,
+        workflow: Option<String>,
+    },
 }
 
 impl FromStr for Source {
     type Err = String;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = input.strip_prefix("catalog:") {
+            let name = name.trim();
+            if name.is_empty() {
+                return Err("Missing app name after 'catalog:'.".into());
+            }
+            return Ok(Source::Catalog(name.to_string()));
+        }
+
+        if input.starts_with("file://") {
+            return match config::file_path(input) {
+                Some(path) => Ok(Source::Local(path)),
+                None => Err(
+                    "Invalid file URL. Use an absolute path like 'file:///path/to/App.AppImage'."
+                        .into(),
+                ),
+            };
+        }
+
+        // Paths win over the shorthands, so `dir/App.AppImage` isn't taken for owner/repo
+        if input.starts_with('/')
+            || input.starts_with("./")
+            || input.starts_with("../")
+            || Path::new(input).is_file()
+        {
+            return Ok(Source::Local(PathBuf::from(input)));
+        }
+
         if input.starts_with("http://") || input.starts_with("https://") {
             if input.contains("github.com") {
                 let clean_input = input
@@ -140,7 +527,21 @@ impl FromStr for Source {
             }
         }
 
-        Err("Invalid source. Use 'owner/repo' for GitHub or a full URL.".into())
+        // Bare package name from a package index
+        let name = input.trim();
+        if !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        {
+            return Ok(Source::Index(name.to_string()));
+        }
+
+        Err(
+            "Invalid source. Use 'owner/repo' for GitHub, a full URL, a local path, \
+             'catalog:<AppName>' or a package name from an index."
+                .into(),
+        )
     }
 }
 
@@ -1,24 +1,196 @@
 use crate::{
-    cli::{AddArgs, RemoveArgs, RenameArgs, RunArgs, Source as CliSource, UpdateArgs},
-    config::{AxePaths, PackageEntry, Source},
-    download, github,
+    appimage, archive, cache, catalog,
+    cli::{
+        AddArgs, AdoptArgs, CacheArgs, CacheCommands, CleanArgs, DataArgs, InfoArgs, RemoveArgs,
+        RenameArgs, RepoArgs, RepoCommands, RunArgs, SandboxArgs, SearchArgs, SetArgsArgs,
+        SetEnvArgs, SetPortableArgs, SetRunModeArgs, Source as CliSource, UpdateArgs,
+    },
+    config::{
+        self, AxePaths, Lockfile, PackageEntry, RemoteFile, Resolution, RunMode, SandboxProfile,
+        Source,
+    },
+    doctor::{self, Status},
+    download,
+    github::{self, RepoMetadata},
+    host,
+    index::{self, IndexEntry},
+    launch,
+    output::{
+        self, AppImageRecord, InfoRecord, ListRecord, OutdatedRecord, OutputFormat, VerifyRecord,
+    },
+    page,
+    signature::{self, Signature},
 };
+use clap::ValueEnum;
+use indicatif::HumanBytes;
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashSet},
     fs,
     io::{self, Write},
-    process::Command,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
 };
-use target_lexicon::{Architecture, Triple};
 
-pub async fn handle_add(add_args: AddArgs, paths: &AxePaths) {
-    let triple = Triple::host();
-    let arch = match triple.architecture {
-        Architecture::X86_64 => "x86_64",
-        Architecture::Aarch64(_) => "aarch64",
-        _ => "x86_64",
+/// Reported for everything that would need the network while `--offline` is set
+const OFFLINE: &str = "not connecting because --offline is set";
+
+/// Keeps a copy of a freshly downloaded AppImage so it can be reinstalled offline
+fn cache_binary(paths: &AxePaths, path: &Path, hash: &str) {
+    if let Err(e) = cache::store(paths, path, hash) {
+        eprintln!("Warning: Failed to cache {:?}: {}", path, e);
+    }
+}
+
+/// Armored public key files from the config that may sign AppImages
+fn trusted_keys(paths: &AxePaths) -> Vec<PathBuf> {
+    paths
+        .load_config()
+        .map(|c| c.trusted_keys)
+        .unwrap_or_default()
+}
+
+/// Verifies the embedded signature of a download and returns the signer's fingerprint
+///
+/// Once a package was seen signed, later downloads must be signed by the same key
+/// unless the new key is listed in `trusted_keys`.
+fn check_signature(
+    path: &Path,
+    expected: Option<&str>,
+    trusted_keys: &[PathBuf],
+) -> Result<Option<String>, String> {
+    match signature::verify(path, trusted_keys)? {
+        Signature::Valid {
+            fingerprint,
+            trusted,
+        } => match expected {
+            Some(expected) if !expected.eq_ignore_ascii_case(&fingerprint) && !trusted => {
+                Err(format!(
+                    "Signed by {}, but earlier versions were signed by {}. \
+                     If the key change is expected, add the new key to trusted_keys in config.toml",
+                    fingerprint, expected
+                ))
+            }
+            _ => {
+                println!("Valid signature by {}", fingerprint);
+                Ok(Some(fingerprint))
+            }
+        },
+        Signature::Unsigned => match expected {
+            Some(expected) => Err(format!(
+                "The download is not signed, but earlier versions were signed by {}",
+                expected
+            )),
+            None => Ok(None),
+        },
+        Signature::Unverified(reason) => match expected {
+            Some(expected) => Err(format!(
+                "Can't check that the download is signed by {}: {}",
+                expected, reason
+            )),
+            None => {
+                eprintln!("Warning: {}", reason);
+                Ok(None)
+            }
+        },
+    }
+}
+
+/// A command line source resolved to the GitHub repo or URL it refers to
+struct ResolvedSource {
+    source: CliSource,
+    catalog_id: Option<String>,
+    /// Entry of the package index the source came from, with its recommended flags
+    index_entry: Option<IndexEntry>,
+}
+
+/// Resolves bare names through the package indexes and `catalog:` sources through the catalog
+async fn resolve_source(source: CliSource, paths: &AxePaths) -> Result<ResolvedSource, String> {
+    let (source, index_entry) = match source {
+        CliSource::Index(package) => {
+            let (index, entry) = index::resolve(paths, &package).await?;
+            println!("Found {} in package index '{}'.", package, index);
+            (entry.source.parse::<CliSource>()?, Some(entry))
+        }
+        source => (source, None),
+    };
+
+    let (source, catalog_id) = match source {
+        CliSource::Catalog(name) => {
+            let (id, underlying) = catalog::resolve(paths, &name).await?;
+            println!("Found {} in the AppImageHub catalog.", id);
+            (underlying.parse::<CliSource>()?, Some(id))
+        }
+        source => (source, None),
+    };
+
+    if let CliSource::Catalog(_) | CliSource::Index(_) = source {
+        return Err("Index entries must point to a GitHub repo, a URL or a catalog app".into());
+    }
+    // The catalog is downloaded, like remote indexes it must not pick files on this machine
+    if catalog_id.is_some() && matches!(source, CliSource::Local(_)) {
+        return Err("Catalog apps can't point to local files".into());
+    }
+
+    Ok(ResolvedSource {
+        source,
+        catalog_id,
+        index_entry,
+    })
+}
+
+pub async fn handle_add(add_args: AddArgs, paths: &AxePaths, arch: &str, offline: bool) {
+    let source = match (add_args.source, add_args.page, add_args.pattern) {
+        (Some(source), _, _) => source,
+        (None, Some(url), Some(pattern)) => CliSource::Page { url, pattern },
+        _ => unreachable!("clap requires a source or --page with --pattern"),
+    };
+    if offline && !matches!(source, CliSource::Local(_)) {
+        eprintln!("Error: Only local files can be added offline, {}", OFFLINE);
+        std::process::exit(1);
+    }
+
+    let index_name = match &source {
+        CliSource::Index(package) => Some(package.clone()),
+        _ => None,
+    };
+    let resolved = match resolve_source(source, paths).await {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let catalog_id = resolved.catalog_id;
+
+    // --branch switches a GitHub source to the artifacts of its workflow runs
+    let source = match (resolved.source, add_args.branch) {
+        (CliSource::Github { owner, repo }, Some(branch)) => CliSource::Artifact {
+            owner,
+            repo,
+            branch,
+            workflow: add_args.workflow,
+        },
+        (_, Some(_)) => {
+            eprintln!("Error: --branch only works with GitHub sources");
+            std::process::exit(1);
+        }
+        (source, None) => source,
+    };
+
+    // Flags recommended by the index are applied on top of the command line
+    let mut prerelease = add_args.prerelease;
+    let mut desktop = add_args.desktop;
+    let mut portable = add_args.portable;
+    let mut asset_pattern = None;
+    if let Some(entry) = resolved.index_entry {
+        prerelease |= entry.prerelease;
+        desktop |= entry.desktop;
+        portable |= entry.portable;
+        asset_pattern = entry.asset_pattern;
     }
-    .to_string();
-    let (suggested_name, meta_version, url, source) = match add_args.source {
+
+    let (suggested_name, meta_version, url, source, release) = match source {
         CliSource::Github {
             ref owner,
             ref repo,
@@ -27,16 +199,20 @@ pub async fn handle_add(add_args: AddArgs, paths: &AxePaths) {
                 "Checking repository {}/{} for architecture '{}'...",
                 owner, repo, arch
             );
-            match github::find_github_asset(owner, repo, add_args.prerelease, &arch).await {
+            match github::find_github_asset(owner, repo, prerelease, asset_pattern.as_deref(), arch)
+                .await
+            {
                 Ok(meta) => (
                     repo.clone(),
-                    meta.version,
-                    meta.asset.browser_download_url,
+                    meta.version.clone(),
+                    meta.asset.browser_download_url.clone(),
                     Source::Github {
                         owner: owner.clone(),
                         repo: repo.clone(),
-                        prerelease: add_args.prerelease,
+                        prerelease,
+                        asset_pattern,
                     },
+                    Some(meta),
                 ),
                 Err(e) => {
                     eprintln!("Error: {}", e);
@@ -50,16 +226,144 @@ pub async fn handle_add(add_args: AddArgs, paths: &AxePaths) {
                 .next_back()
                 .unwrap_or("appimage")
                 .trim_end_matches(".AppImage")
-                .trim_end_matches(".appimage");
+                .trim_end_matches(".appimage")
+                .trim_end_matches(".tar.gz")
+                .trim_end_matches(".tgz")
+                .trim_end_matches(".zip");
+
+            // Recorded so `axe update` can tell when the file behind the URL changes
+            let remote = match download::probe(url).await {
+                Ok(remote) => remote,
+                Err(e) => {
+                    eprintln!("Warning: {}", e);
+                    RemoteFile::default()
+                }
+            };
 
             (
                 suggested_name.to_string(),
-                "unknown".to_string(),
+                remote
+                    .version_hint(url)
+                    .unwrap_or_else(|| "unknown".to_string()),
                 url.clone(),
-                Source::Direct,
+                Source::Direct(remote),
+                None,
+            )
+        }
+        CliSource::Artifact {
+            ref owner,
+            ref repo,
+            ref branch,
+            ref workflow,
+        } => {
+            println!(
+                "Checking workflow runs of {}/{} on branch {}...",
+                owner, repo, branch
+            );
+            match github::find_workflow_artifact(owner, repo, branch, workflow.as_deref(), arch)
+                .await
+            {
+                Ok(artifact) => {
+                    println!(
+                        "Found artifact {} of run {}.",
+                        artifact.name, artifact.run_id
+                    );
+                    (
+                        repo.clone(),
+                        artifact.run_id.to_string(),
+                        artifact.url,
+                        Source::Artifact {
+                            owner: owner.clone(),
+                            repo: repo.clone(),
+                            branch: branch.clone(),
+                            workflow: workflow.clone(),
+                        },
+                        None,
+                    )
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        CliSource::Page {
+            ref url,
+            ref pattern,
+        } => {
+            println!("Looking for '{}' on {}...", pattern, url);
+            match page::find_latest(url, pattern).await {
+                Ok(link) => {
+                    let suggested_name = link
+                        .url
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or("appimage")
+                        .split(['-', '_', '.'])
+                        .next()
+                        .filter(|n| !n.is_empty())
+                        .unwrap_or("appimage");
+                    (
+                        suggested_name.to_string(),
+                        page_link_version(&link),
+                        link.url,
+                        Source::Page {
+                            page: url.clone(),
+                            pattern: pattern.clone(),
+                        },
+                        None,
+                    )
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        CliSource::Local(ref path) => {
+            let path = match fs::canonicalize(path) {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("Error: Cannot read {:?}: {}", path, e);
+                    std::process::exit(1);
+                }
+            };
+            let hash = match download::calculate_hash(&path) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    eprintln!("Error: Cannot read {:?}: {}", path, e);
+                    std::process::exit(1);
+                }
+            };
+            let suggested_name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "appimage".to_string());
+            let suggested_name = suggested_name
+                .trim_end_matches(".AppImage")
+                .trim_end_matches(".appimage")
+                .trim_end_matches(".tar.gz")
+                .trim_end_matches(".tgz")
+                .trim_end_matches(".zip");
+
+            (
+                suggested_name.to_string(),
+                local_version(&hash),
+                config::file_url(&path),
+                Source::Local {
+                    modified: download::modified_time(&path),
+                },
+                None,
             )
         }
+        CliSource::Catalog(_) | CliSource::Index(_) => {
+            unreachable!("catalog and index sources are resolved above")
+        }
     };
+    let suggested_name = catalog_id
+        .as_ref()
+        .map(|id| id.to_lowercase().replace(' ', "-"))
+        .unwrap_or(suggested_name);
 
     let mut lockfile = paths.load_lockfile().unwrap_or_default();
 
@@ -79,12 +383,32 @@ pub async fn handle_add(add_args: AddArgs, paths: &AxePaths) {
                     ..
                 },
             ) => o1.to_lowercase() == o2.to_lowercase() && r1.to_lowercase() == r2.to_lowercase(),
-            (Source::Direct, Source::Direct) => p.url == url,
+            (Source::Direct(_), Source::Direct(_)) => p.url == url,
+            (Source::Local { .. }, Source::Local { .. }) => p.url == url,
+            (Source::Page { page: p1, .. }, Source::Page { page: p2, .. }) => p1 == p2,
+            (
+                Source::Artifact {
+                    owner: o1,
+                    repo: r1,
+                    branch: b1,
+                    ..
+                },
+                Source::Artifact {
+                    owner: o2,
+                    repo: r2,
+                    branch: b2,
+                    ..
+                },
+            ) => {
+                o1.to_lowercase() == o2.to_lowercase()
+                    && r1.to_lowercase() == r2.to_lowercase()
+                    && b1 == b2
+            }
             _ => false,
         })
         .map(|p| p.name.clone());
 
-    let name = if let Some(n) = add_args.name {
+    let name = if let Some(n) = add_args.name.or(index_name) {
         n
     } else if let Some(ref ename) = existing_name {
         ename.clone()
@@ -103,6 +427,11 @@ pub async fn handle_add(add_args: AddArgs, paths: &AxePaths) {
         }
     };
 
+    if let Err(e) = config::validate_name(&name) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
     if let Some(existing) = lockfile.packages.get(&name) {
         if existing.version == meta_version {
             println!("{} version {} is already installed.", name, meta_version);
@@ -111,31 +440,43 @@ pub async fn handle_add(add_args: AddArgs, paths: &AxePaths) {
         println!("Updating {} to version {}...", name, meta_version);
     }
 
-    let file_name = url.split('/').next_back().unwrap_or(&name);
-    let dest = paths.bin_dir.join(file_name);
+    let upstream_file = url.split('/').next_back().map(String::from);
+    let dest = paths.binary_path(&name);
 
-    let hash = if dest.exists() {
-        println!("Binary already exists at {:?}. Calculating hash...", dest);
-        match download::calculate_hash(&dest) {
-            Ok(h) => {
-                download::set_executable(&dest).expect("Failed to set executable permissions");
-                h
-            }
+    let expected_signer = lockfile.packages.get(&name).and_then(|p| p.signer.clone());
+    let keys = trusted_keys(paths);
+
+    let expected_hash = match &release {
+        Some(meta) => match github::expected_hash(meta).await {
+            Ok(hash) => hash,
             Err(e) => {
-                eprintln!("Failed to calculate hash: {}. Re-downloading...", e);
-                download::download_file(&url, dest.clone(), &name)
-                    .await
-                    .expect("Failed to download")
+                eprintln!("Failed to install {}: {}", name, e);
+                std::process::exit(1);
             }
+        },
+        None => None,
+    };
+
+    println!("Downloading {}...", name);
+    let downloaded = match download::download_file(
+        &url,
+        dest.clone(),
+        &name,
+        arch,
+        expected_hash.as_deref(),
+        None,
+        |part| check_signature(part, expected_signer.as_deref(), &keys),
+    )
+    .await
+    {
+        Ok(downloaded) => downloaded,
+        Err(e) => {
+            eprintln!("Failed to install {}: {}", name, e);
+            std::process::exit(1);
         }
-    } else {
-        println!("Downloading {}...", name);
-        download::download_file(&url, dest.clone(), &name)
-            .await
-            .expect("Failed to download")
     };
 
-    let should_create_desktop = if add_args.yes || add_args.desktop {
+    let should_create_desktop = if add_args.yes || desktop {
         true
     } else {
         print!("Create a desktop entry for {}? [Y/n]: ", name);
@@ -146,57 +487,226 @@ pub async fn handle_add(add_args: AddArgs, paths: &AxePaths) {
         response.is_empty() || response == "y" || response == "yes"
     };
 
-    let desktop_file = if should_create_desktop {
-        match create_desktop_file(&name, &dest, paths) {
-            Ok(p) => Some(p),
-            Err(e) => {
-                eprintln!("Warning: Failed to create desktop file: {}", e);
-                None
+    if let Err(e) = link_binary(&name, &dest, paths) {
+        eprintln!("Warning: Failed to link binary: {}", e);
+    }
+    cache_binary(paths, &dest, &downloaded.hash);
+
+    // A local file may have changed since it was hashed, the copy is what got installed
+    let meta_version = match source {
+        Source::Local { .. } => local_version(&downloaded.hash),
+        _ => meta_version,
+    };
+
+    // Re-adding a package keeps its per-package settings
+    let mut entry = match lockfile.packages.remove(&name) {
+        Some(existing) => {
+            move_portable_dirs(&existing.path, &dest);
+            if existing.path != dest
+                && existing.path.exists()
+                && let Err(e) = fs::remove_file(&existing.path)
+            {
+                eprintln!("Warning: Failed to remove old binary: {}", e);
+            }
+            PackageEntry {
+                version: meta_version,
+                url,
+                hash: downloaded.hash,
+                path: dest,
+                upstream_file,
+                installed_at: Some(config::now()),
+                catalog_id: catalog_id.or(existing.catalog_id.clone()),
+                signer: downloaded.checked,
+                arch: Some(arch.to_string()),
+                archive_path: downloaded.archive_path,
+                archive_hash: downloaded.archive_hash,
+                resolutions: BTreeMap::new(),
+                source,
+                ..existing
             }
         }
-    } else {
-        None
+        None => PackageEntry {
+            upstream_file,
+            catalog_id,
+            signer: downloaded.checked,
+            arch: Some(arch.to_string()),
+            archive_path: downloaded.archive_path,
+            archive_hash: downloaded.archive_hash,
+            ..PackageEntry::new(
+                name.clone(),
+                meta_version,
+                url,
+                downloaded.hash,
+                dest,
+                source,
+            )
+        },
     };
 
-    lockfile.packages.insert(
-        name.clone(),
-        PackageEntry {
-            name: name.clone(),
-            version: meta_version,
-            url,
-            hash,
-            path: dest,
-            desktop_file,
-            source,
-        },
-    );
+    if portable {
+        entry.portable = true;
+    }
+    if entry.portable
+        && let Err(e) = create_portable_dirs(&entry)
+    {
+        eprintln!("Warning: {}", e);
+    }
+
+    if should_create_desktop || entry.desktop_file.is_some() {
+        match create_desktop_file(&entry, paths) {
+            Ok(p) => entry.desktop_file = Some(p),
+            Err(e) => eprintln!("Warning: Failed to create desktop file: {}", e),
+        }
+    }
+
+    lockfile.packages.insert(name.clone(), entry);
     paths
         .save_lockfile(&lockfile)
         .expect("Failed to save lockfile");
     println!("Successfully installed {}!", name);
 }
 
-fn create_desktop_file(
-    name: &str,
-    exec_path: &std::path::Path,
-    paths: &AxePaths,
-) -> Result<std::path::PathBuf, String> {
-    let desktop_path = paths.applications_dir.join(format!("{}.desktop", name));
+/// Key added to every desktop file axe writes, so they can be told apart from others
+const DESKTOP_MARKER: &str = "X-Axe-Package";
 
-    let content = format!(
-        "[Desktop Entry]\nType=Application\nName={}\nExec={}\nIcon=utilities-terminal\nTerminal=false\nCategories=Utility;\n",
-        name,
-        exec_path.to_string_lossy(),
+fn create_desktop_file(pkg: &PackageEntry, paths: &AxePaths) -> Result<PathBuf, String> {
+    let desktop_path = paths.applications_dir.join(format!("{}.desktop", pkg.name));
+
+    let mut content = format!(
+        "[Desktop Entry]\nType=Application\nName={}\nExec={}\nIcon=utilities-terminal\nTerminal=false\nCategories=Utility;\n{}={}\n",
+        pkg.name,
+        launch::desktop_exec(pkg, paths),
+        DESKTOP_MARKER,
+        pkg.name,
     );
+    if let Some(dir) = &pkg.working_dir {
+        content.push_str(&format!("Path={}\n", dir.to_string_lossy()));
+    }
 
     fs::write(&desktop_path, content)
         .map_err(|e| format!("Failed to write desktop file: {}", e))?;
     Ok(desktop_path)
 }
 
-pub fn handle_list(paths: &AxePaths) {
+/// Rewrites the desktop entry after launch settings changed, if the package has one
+fn refresh_desktop_file(pkg: &PackageEntry, paths: &AxePaths) {
+    if pkg.desktop_file.is_some()
+        && let Err(e) = create_desktop_file(pkg, paths)
+    {
+        eprintln!("Warning: Failed to update desktop file: {}", e);
+    }
+}
+
+/// Case-insensitive lookup that exits when the package doesn't exist
+fn find_package_mut<'a>(lockfile: &'a mut Lockfile, name: &str) -> &'a mut PackageEntry {
+    match lockfile
+        .packages
+        .values_mut()
+        .find(|p| p.name.to_lowercase() == name.to_lowercase())
+    {
+        Some(p) => p,
+        None => {
+            eprintln!("Package '{}' not found in lockfile.", name);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn create_portable_dirs(pkg: &PackageEntry) -> Result<(), String> {
+    for dir in pkg.portable_dirs() {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    }
+    Ok(())
+}
+
+fn move_portable_dirs(old_path: &Path, new_path: &Path) {
+    if old_path == new_path {
+        return;
+    }
+
+    for (old_dir, new_dir) in config::portable_dirs(old_path)
+        .into_iter()
+        .zip(config::portable_dirs(new_path))
+    {
+        if old_dir.is_dir()
+            && !new_dir.exists()
+            && let Err(e) = fs::rename(&old_dir, &new_dir)
+        {
+            eprintln!(
+                "Warning: Failed to move portable data {:?} to {:?}: {}",
+                old_dir, new_dir, e
+            );
+        }
+    }
+}
+
+fn dir_size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| dir_size(&e.path()))
+            .sum(),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Symlinks the binary into the link dir so it is on every user's PATH (system mode only)
+fn link_binary(name: &str, target: &Path, paths: &AxePaths) -> Result<(), String> {
+    let Some(link_dir) = &paths.link_dir else {
+        return Ok(());
+    };
+    let link = link_dir.join(name);
+
+    if link.is_symlink() {
+        fs::remove_file(&link).map_err(|e| format!("Failed to remove old link: {}", e))?;
+    } else if link.exists() {
+        return Err(format!(
+            "{:?} already exists and is not managed by axe",
+            link
+        ));
+    }
+
+    symlink(target, &link).map_err(|e| format!("Failed to create link {:?}: {}", link, e))
+}
+
+fn unlink_binary(name: &str, paths: &AxePaths) {
+    let Some(link_dir) = &paths.link_dir else {
+        return;
+    };
+    let link = link_dir.join(name);
+
+    if link.is_symlink()
+        && let Err(e) = fs::remove_file(&link)
+    {
+        eprintln!("Warning: Failed to remove link {:?}: {}", link, e);
+    }
+}
+
+pub fn handle_list(paths: &AxePaths, format: OutputFormat) {
     let lockfile = paths.load_lockfile().unwrap_or_default();
-    if lockfile.packages.is_empty() {
+    let mut packages: Vec<_> = lockfile.packages.into_values().collect();
+    packages.sort_by_key(|p| p.name.to_lowercase());
+
+    if format != OutputFormat::Text {
+        let records: Vec<ListRecord> = packages
+            .into_iter()
+            .map(|pkg| ListRecord {
+                source: pkg.source.kind().to_string(),
+                origin: pkg.origin(),
+                installed: pkg.path.exists(),
+                name: pkg.name,
+                version: pkg.version,
+                path: pkg.path,
+            })
+            .collect();
+        output::print(format, records);
+        return;
+    }
+
+    if packages.is_empty() {
         println!("No packages tracked in lockfile.");
         return;
     }
@@ -204,37 +714,158 @@ pub fn handle_list(paths: &AxePaths) {
     println!("{:<30} {:<25} {:<15}", "NAME", "VERSION", "STATUS");
     println!("{}", "-".repeat(70));
 
-    for (name, pkg) in lockfile.packages {
+    for pkg in packages {
         let status = if pkg.path.exists() {
             "Installed"
         } else {
             "Missing"
         };
-        println!("{:<30} {:<25} {:<15}", name, pkg.version, status);
+        println!("{:<30} {:<25} {:<15}", pkg.name, pkg.version, status);
     }
 }
 
-pub async fn handle_install(paths: &AxePaths) {
-    let lockfile = paths.load_lockfile().unwrap_or_default();
+/// SHA-256 the download of a recorded file must have: the archive's for packed AppImages
+fn recorded_download_hash(recorded: &Resolution) -> Option<&str> {
+    match &recorded.archive_path {
+        Some(_) => recorded.archive_hash.as_deref(),
+        None => Some(recorded.hash.as_str()).filter(|h| !h.is_empty()),
+    }
+}
+
+/// Checks an AppImage unpacked from an archive against the lockfile, which can only be done
+/// once it is out
+fn check_unpacked_hash(part: &Path, name: &str, recorded: &Resolution) -> Result<(), String> {
+    if recorded.archive_path.is_none() || recorded.hash.is_empty() {
+        return Ok(());
+    }
+    let hash = download::calculate_hash(part)?;
+    if !hash.eq_ignore_ascii_case(&recorded.hash) {
+        return Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            name, recorded.hash, hash
+        ));
+    }
+    Ok(())
+}
+
+pub async fn handle_install(paths: &AxePaths, arch: &str, offline: bool) {
+    let mut lockfile = paths.load_lockfile().unwrap_or_default();
     if lockfile.packages.is_empty() {
         println!("Nothing to install.");
         return;
     }
 
-    for (name, pkg) in lockfile.packages {
+    let keys = trusted_keys(paths);
+    let mut changed = false;
+    for (name, pkg) in lockfile.packages.iter_mut() {
+        // A shared lockfile may have been resolved on a machine with another architecture.
+        // GitHub releases are then resolved for this one and recorded next to the original.
+        let recorded = pkg.resolution(arch);
+        let file_arch = match &recorded {
+            Some(r) if r.url == pkg.url => pkg.arch.clone().unwrap_or_else(|| arch.to_string()),
+            _ => arch.to_string(),
+        };
+
+        if !pkg.path.exists()
+            && let Some(recorded) = &recorded
+        {
+            match cache::restore(paths, &recorded.hash, &pkg.path) {
+                Ok(true) => println!("Restored {} from the download cache.", name),
+                Ok(false) => {}
+                Err(e) => eprintln!("Warning: Failed to restore {} from the cache: {}", name, e),
+            }
+        }
+
         // 1. Check/Install binary
         if !pkg.path.exists() {
             println!("Installing missing binary: {}...", name);
-            match download::download_file(&pkg.url, pkg.path.clone(), &name).await {
-                Ok(_) => println!("Successfully installed binary for {}!", name),
+            let target = match (&recorded, &pkg.source) {
+                _ if offline => Err(format!("it is not in the download cache, {}", OFFLINE)),
+                // The lockfile pins the exact file, whatever the server offers now
+                (Some(recorded), _) => Ok((
+                    recorded.url.clone(),
+                    recorded_download_hash(recorded).map(String::from),
+                )),
+                (
+                    None,
+                    Source::Github {
+                        owner,
+                        repo,
+                        asset_pattern,
+                        ..
+                    },
+                ) => {
+                    println!("Resolving {} {} for {}...", name, pkg.version, arch);
+                    match github::find_release_asset(
+                        owner,
+                        repo,
+                        &pkg.version,
+                        asset_pattern.as_deref(),
+                        arch,
+                    )
+                    .await
+                    {
+                        Ok(meta) => github::expected_hash(&meta)
+                            .await
+                            .map(|hash| (meta.asset.browser_download_url, hash)),
+                        Err(e) => Err(e),
+                    }
+                }
+                (None, _) => Err(format!("no file is recorded for {}", arch)),
+            };
+
+            let result = match target {
+                Ok((url, expected_hash)) => download::download_file(
+                    &url,
+                    pkg.path.clone(),
+                    name,
+                    &file_arch,
+                    expected_hash.as_deref(),
+                    recorded.as_ref().and_then(|r| r.archive_path.as_deref()),
+                    |part| {
+                        if let Some(recorded) = &recorded {
+                            check_unpacked_hash(part, name, recorded)?;
+                        }
+                        check_signature(part, pkg.signer.as_deref(), &keys)
+                    },
+                )
+                .await
+                .map(|downloaded| (url, downloaded)),
+                Err(e) => Err(e),
+            };
+
+            match result {
+                Ok((url, downloaded)) => {
+                    cache_binary(paths, &pkg.path, &downloaded.hash);
+                    if recorded.is_none() {
+                        pkg.resolutions.insert(
+                            arch.to_string(),
+                            Resolution {
+                                version: pkg.version.clone(),
+                                url,
+                                hash: downloaded.hash,
+                                archive_path: downloaded.archive_path,
+                                archive_hash: downloaded.archive_hash,
+                            },
+                        );
+                        changed = true;
+                    }
+                    println!("Successfully installed binary for {}!", name);
+                }
                 Err(e) => eprintln!("Failed to install binary for {}: {}", name, e),
             }
         }
 
+        if pkg.path.exists()
+            && let Err(e) = link_binary(name, &pkg.path, paths)
+        {
+            eprintln!("Warning: Failed to link binary for {}: {}", name, e);
+        }
+
         // 2. Check/Restore desktop file
         if let Some(_desktop_path) = pkg.desktop_file.as_ref().filter(|p| !p.exists()) {
             println!("Restoring desktop entry for {}...", name);
-            if let Err(e) = create_desktop_file(&name, &pkg.path, paths) {
+            if let Err(e) = create_desktop_file(pkg, paths) {
                 eprintln!(
                     "Warning: Failed to restore desktop file for {}: {}",
                     name, e
@@ -242,9 +873,15 @@ pub async fn handle_install(paths: &AxePaths) {
             }
         }
     }
+
+    if changed {
+        paths
+            .save_lockfile(&lockfile)
+            .expect("Failed to save lockfile");
+    }
 }
 
-pub async fn handle_run(args: RunArgs, paths: &AxePaths) {
+pub async fn handle_run(args: RunArgs, paths: &AxePaths, offline: bool) {
     let lockfile = paths.load_lockfile().unwrap_or_default();
 
     // Case-insensitive lookup
@@ -260,7 +897,45 @@ pub async fn handle_run(args: RunArgs, paths: &AxePaths) {
         }
     };
 
+    // The file `axe install` resolved for this machine, or else the one the package was
+    // installed with, for the architecture it was installed for
+    let (recorded, file_arch) = match pkg.resolution(host::arch()) {
+        Some(r) if r.url != pkg.url => (r, host::arch()),
+        _ => {
+            let arch = pkg.arch.as_deref().unwrap_or(host::arch());
+            (pkg.resolution(arch).unwrap_or_default(), arch)
+        }
+    };
+
+    if !pkg.path.exists() && paths.system && !host::is_root() {
+        eprintln!(
+            "Package '{}' is not installed in {:?}, installing it requires root privileges.",
+            args.name, paths.bin_dir
+        );
+        eprintln!("Please re-run the command with sudo.");
+        std::process::exit(1);
+    }
+
+    if !pkg.path.exists() {
+        match cache::restore(paths, &recorded.hash, &pkg.path) {
+            Ok(true) => println!("Restored {} from the download cache.", args.name),
+            Ok(false) => {}
+            Err(e) => eprintln!(
+                "Warning: Failed to restore {} from the cache: {}",
+                args.name, e
+            ),
+        }
+    }
+
     if !pkg.path.exists() {
+        if offline {
+            eprintln!(
+                "Package '{}' is not installed and not in the download cache, {}.",
+                args.name, OFFLINE
+            );
+            std::process::exit(1);
+        }
+
         let should_download = if args.yes {
             true
         } else {
@@ -278,8 +953,25 @@ pub async fn handle_run(args: RunArgs, paths: &AxePaths) {
 
         if should_download {
             println!("Installing {}...", args.name);
-            match download::download_file(&pkg.url, pkg.path.clone(), &args.name).await {
-                Ok(_) => println!("Successfully installed {}!", args.name),
+            let keys = trusted_keys(paths);
+            match download::download_file(
+                &recorded.url,
+                pkg.path.clone(),
+                &args.name,
+                file_arch,
+                recorded_download_hash(&recorded),
+                recorded.archive_path.as_deref(),
+                |part| {
+                    check_unpacked_hash(part, &args.name, &recorded)?;
+                    check_signature(part, pkg.signer.as_deref(), &keys)
+                },
+            )
+            .await
+            {
+                Ok(downloaded) => {
+                    cache_binary(paths, &pkg.path, &downloaded.hash);
+                    println!("Successfully installed {}!", args.name);
+                }
                 Err(e) => {
                     eprintln!("Failed to install {}: {}", args.name, e);
                     std::process::exit(1);
@@ -291,7 +983,13 @@ pub async fn handle_run(args: RunArgs, paths: &AxePaths) {
         }
     }
 
-    let mut cmd = Command::new(&pkg.path);
+    let mut cmd = match launch::command(pkg, paths) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
     cmd.args(&args.args);
 
     match cmd.status() {
@@ -322,6 +1020,11 @@ pub fn handle_rename(args: RenameArgs, paths: &AxePaths) {
         }
     };
 
+    if let Err(e) = config::validate_name(&args.new_name) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
     if lockfile.packages.contains_key(&args.new_name) {
         eprintln!("Package '{}' already exists in lockfile.", args.new_name);
         std::process::exit(1);
@@ -329,13 +1032,27 @@ pub fn handle_rename(args: RenameArgs, paths: &AxePaths) {
 
     let mut pkg = lockfile.packages.remove(&old_name_internal).unwrap();
 
+    pkg.name = args.new_name.clone();
+
+    // The binary is named after the package, so it moves along with its portable data
+    let new_path = paths.binary_path(&args.new_name);
+    if pkg.path.exists() && pkg.path != new_path {
+        match fs::rename(&pkg.path, &new_path) {
+            Ok(_) => {
+                move_portable_dirs(&pkg.path, &new_path);
+                pkg.path = new_path;
+            }
+            Err(e) => eprintln!("Warning: Failed to rename binary: {}", e),
+        }
+    }
+
     // Update desktop file if it exists
-    if let Some(old_desktop) = pkg.desktop_file {
+    if let Some(old_desktop) = pkg.desktop_file.take() {
         if let Err(e) = fs::remove_file(&old_desktop) {
             eprintln!("Warning: Failed to remove old desktop file: {}", e);
         }
 
-        match create_desktop_file(&args.new_name, &pkg.path, paths) {
+        match create_desktop_file(&pkg, paths) {
             Ok(new_desktop) => pkg.desktop_file = Some(new_desktop),
             Err(e) => {
                 eprintln!("Warning: Failed to create new desktop file: {}", e);
@@ -344,7 +1061,18 @@ pub fn handle_rename(args: RenameArgs, paths: &AxePaths) {
         }
     }
 
-    pkg.name = args.new_name.clone();
+    let old_extracted = paths.extracted_dir(&old_name_internal);
+    if old_extracted.exists()
+        && let Err(e) = fs::rename(&old_extracted, paths.extracted_dir(&args.new_name))
+    {
+        eprintln!("Warning: Failed to move extracted files: {}", e);
+    }
+
+    unlink_binary(&old_name_internal, paths);
+    if let Err(e) = link_binary(&args.new_name, &pkg.path, paths) {
+        eprintln!("Warning: Failed to link binary: {}", e);
+    }
+
     lockfile.packages.insert(args.new_name.clone(), pkg);
 
     paths
@@ -356,30 +1084,94 @@ pub fn handle_rename(args: RenameArgs, paths: &AxePaths) {
     );
 }
 
-pub async fn handle_update(args: UpdateArgs, paths: &AxePaths) {
-    let triple = Triple::host();
-    let arch = match triple.architecture {
-        Architecture::X86_64 => "x86_64",
-        Architecture::Aarch64(_) => "aarch64",
-        _ => "x86_64",
-    };
-    let mut lockfile = paths.load_lockfile().expect("Failed to load lockfile");
-    let mut updated_packages = Vec::new();
+/// An update selected by `axe update`, downloaded once all packages are checked
+struct PendingUpdate {
+    name: String,
+    version: String,
+    url: String,
+    /// The GitHub release, for checksum lookup
+    release: Option<RepoMetadata>,
+    /// What the server reported for a direct URL, recorded once downloaded
+    remote: Option<RemoteFile>,
+    /// Architecture the package is updated for
+    arch: String,
+}
 
-    if lockfile.packages.is_empty() {
-        println!("No packages tracked in lockfile.");
-        return;
+fn confirm_update(name: &str, version: &str, yes: bool) -> bool {
+    if yes {
+        return true;
     }
+    print!("Update {} to {}? [Y/n]: ", name, version);
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    let input = input.trim().to_lowercase();
+    input.is_empty() || input == "y" || input == "yes"
+}
 
-    for (name, pkg) in &lockfile.packages {
-        match &pkg.source {
-            Source::Github {
-                owner,
-                repo,
-                prerelease,
+/// Version of a scraped link: the captured version, or the file name without one
+fn page_link_version(link: &page::PageLink) -> String {
+    link.version
+        .clone()
+        .unwrap_or_else(|| link.url.rsplit('/').next().unwrap_or_default().to_string())
+}
+
+/// Version recorded for local sources: a prefix of the source file's SHA-256
+fn local_version(hash: &str) -> String {
+    hash.chars().take(12).collect()
+}
+
+/// Checks a local source for a new build and returns its version
+///
+/// The file is only hashed when its modification time differs from `modified`.
+fn check_local_source(pkg: &PackageEntry, modified: Option<u64>) -> Result<Option<String>, String> {
+    let path = PathBuf::from(pkg.origin());
+    if !path.is_file() {
+        return Err(format!("{:?} is not available", path));
+    }
+    if modified.is_some() && download::modified_time(&path) == modified {
+        return Ok(None);
+    }
+    let version = local_version(&download::calculate_hash(&path)?);
+    Ok((version != pkg.version).then_some(version))
+}
+
+/// Updates all packages, each for the architecture it was installed for unless `arch` is given
+pub async fn handle_update(args: UpdateArgs, paths: &AxePaths, arch: Option<&str>, offline: bool) {
+    let mut lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let mut updated_packages: Vec<PendingUpdate> = Vec::new();
+    let mut failed = false;
+
+    if lockfile.packages.is_empty() {
+        println!("No packages tracked in lockfile.");
+        return;
+    }
+
+    for (name, pkg) in &lockfile.packages {
+        let arch = arch.or(pkg.arch.as_deref()).unwrap_or(host::arch());
+        if offline && !matches!(pkg.source, Source::Local { .. }) {
+            eprintln!("Failed to check updates for {}: {}", name, OFFLINE);
+            failed = true;
+            continue;
+        }
+
+        match &pkg.source {
+            Source::Github {
+                owner,
+                repo,
+                prerelease,
+                asset_pattern,
             } => {
                 println!("Checking update for {} ({}/{})...", name, owner, repo);
-                match github::find_github_asset(owner, repo, *prerelease, arch).await {
+                match github::find_github_asset(
+                    owner,
+                    repo,
+                    *prerelease,
+                    asset_pattern.as_deref(),
+                    arch,
+                )
+                .await
+                {
                     Ok(meta) => {
                         if meta.version != pkg.version {
                             println!(
@@ -387,23 +1179,125 @@ pub async fn handle_update(args: UpdateArgs, paths: &AxePaths) {
                                 name, pkg.version, meta.version
                             );
 
-                            let should_update = if args.yes {
-                                true
-                            } else {
-                                print!("Update {} to {}? [Y/n]: ", name, meta.version);
-                                io::stdout().flush().unwrap();
-                                let mut input = String::new();
-                                io::stdin().read_line(&mut input).unwrap();
-                                let input = input.trim().to_lowercase();
-                                input.is_empty() || input == "y" || input == "yes"
-                            };
-
-                            if should_update {
-                                updated_packages.push((
-                                    name.clone(),
-                                    meta.version,
-                                    meta.asset.browser_download_url,
-                                ));
+                            if confirm_update(name, &meta.version, args.yes) {
+                                updated_packages.push(PendingUpdate {
+                                    name: name.clone(),
+                                    version: meta.version.clone(),
+                                    url: meta.asset.browser_download_url.clone(),
+                                    release: Some(meta),
+                                    remote: None,
+                                    arch: arch.to_string(),
+                                });
+                            }
+                        } else {
+                            println!("{} is already up to date ({}).", name, pkg.version);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to check updates for {}: {}", name, e);
+                        failed = true;
+                    }
+                }
+            }
+            Source::Direct(recorded) => {
+                println!("Checking update for {} ({})...", name, pkg.url);
+                let remote = match download::probe(&pkg.url).await {
+                    Ok(remote) => remote,
+                    Err(e) => {
+                        eprintln!("Failed to check updates for {}: {}", name, e);
+                        failed = true;
+                        continue;
+                    }
+                };
+                let version = remote
+                    .version_hint(&pkg.url)
+                    .unwrap_or_else(|| "unknown".to_string());
+                match recorded.changed(&remote) {
+                    Some(false) => {
+                        println!("{} is already up to date ({}).", name, pkg.version);
+                        continue;
+                    }
+                    Some(true) => println!("New upload found for {} at {}.", name, pkg.url),
+                    None if recorded.is_empty() => println!(
+                        "No download details recorded for {} yet, downloading it again records them.",
+                        name
+                    ),
+                    None => {
+                        println!(
+                            "Cannot check {} for updates: the server sends no ETag, Last-Modified or Content-Length.",
+                            name
+                        );
+                        continue;
+                    }
+                }
+                if confirm_update(name, &version, args.yes) {
+                    updated_packages.push(PendingUpdate {
+                        name: name.clone(),
+                        version,
+                        url: pkg.url.clone(),
+                        release: None,
+                        remote: Some(remote),
+                        arch: arch.to_string(),
+                    });
+                }
+            }
+            Source::Artifact {
+                owner,
+                repo,
+                branch,
+                workflow,
+            } => {
+                println!("Checking update for {} ({})...", name, pkg.origin());
+                match github::find_workflow_artifact(owner, repo, branch, workflow.as_deref(), arch)
+                    .await
+                {
+                    Ok(artifact) => {
+                        let run_id = artifact.run_id.to_string();
+                        if run_id != pkg.version {
+                            println!(
+                                "New workflow run found for {}: {} -> {}",
+                                name, pkg.version, run_id
+                            );
+                            if confirm_update(name, &run_id, args.yes) {
+                                updated_packages.push(PendingUpdate {
+                                    name: name.clone(),
+                                    version: run_id,
+                                    url: artifact.url,
+                                    release: None,
+                                    remote: None,
+                                    arch: arch.to_string(),
+                                });
+                            }
+                        } else {
+                            println!("{} is already up to date (run {}).", name, pkg.version);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to check updates for {}: {}", name, e);
+                        failed = true;
+                    }
+                }
+            }
+            Source::Page { page, pattern } => {
+                println!("Checking update for {} ({})...", name, page);
+                match page::find_latest(page, pattern).await {
+                    Ok(link) => {
+                        let version = page_link_version(&link);
+                        // Pages may still list older builds, never go back to one
+                        if page::compare_versions(&version, &pkg.version) == Ordering::Greater {
+                            println!(
+                                "New version found for {}: {} -> {}",
+                                name, pkg.version, version
+                            );
+                            if confirm_update(name, &version, args.yes) {
+                                updated_packages.push(PendingUpdate {
+                                    name: name.clone(),
+                                    version,
+                                    url: link.url,
+                                    release: None,
+                                    remote: None,
+                                    arch: arch.to_string(),
+                                });
                             }
                         } else {
                             println!("{} is already up to date ({}).", name, pkg.version);
@@ -411,48 +1305,144 @@ pub async fn handle_update(args: UpdateArgs, paths: &AxePaths) {
                     }
                     Err(e) => {
                         eprintln!("Failed to check updates for {}: {}", name, e);
+                        failed = true;
                     }
                 }
             }
-            Source::Direct => {
-                println!("Skipping update check for {} (Direct URL source).", name);
+            Source::Local { modified } => {
+                println!("Checking update for {} ({})...", name, pkg.origin());
+                match check_local_source(pkg, *modified) {
+                    Ok(Some(version)) => {
+                        println!(
+                            "New build found for {}: {} -> {}",
+                            name, pkg.version, version
+                        );
+                        if confirm_update(name, &version, args.yes) {
+                            updated_packages.push(PendingUpdate {
+                                name: name.clone(),
+                                version,
+                                url: pkg.url.clone(),
+                                release: None,
+                                remote: None,
+                                arch: arch.to_string(),
+                            });
+                        }
+                    }
+                    Ok(None) => println!("{} is already up to date ({}).", name, pkg.version),
+                    Err(e) => {
+                        eprintln!("Failed to check updates for {}: {}", name, e);
+                        failed = true;
+                    }
+                }
             }
         }
     }
 
-    for (name, new_version, new_url) in updated_packages {
+    let keys = trusted_keys(paths);
+    for PendingUpdate {
+        name,
+        version: new_version,
+        url: new_url,
+        release,
+        remote,
+        arch,
+    } in updated_packages
+    {
+        let expected_hash = match &release {
+            Some(meta) => match github::expected_hash(meta).await {
+                Ok(hash) => hash,
+                Err(e) => {
+                    eprintln!("Failed to update {}: {}", name, e);
+                    failed = true;
+                    continue;
+                }
+            },
+            None => None,
+        };
         println!("Updating {} to {}...", name, new_version);
 
         let pkg = lockfile.packages.get(&name).unwrap();
         let old_path = pkg.path.clone();
 
-        let file_name = new_url.split('/').next_back().unwrap_or(&name);
-        let new_dest = paths.bin_dir.join(file_name);
+        let new_dest = paths.binary_path(&name);
+        let expected_signer = pkg.signer.clone();
 
-        match download::download_file(&new_url, new_dest.clone(), &name).await {
-            Ok(hash) => {
+        match download::download_file(
+            &new_url,
+            new_dest.clone(),
+            &name,
+            &arch,
+            expected_hash.as_deref(),
+            None,
+            |part| check_signature(part, expected_signer.as_deref(), &keys),
+        )
+        .await
+        {
+            Ok(downloaded) => {
                 // Remove old file if it's different from the new one
-                if old_path.exists() && old_path != new_dest {
-                    let _ = fs::remove_file(&old_path);
+                if old_path.exists()
+                    && old_path != new_dest
+                    && let Err(e) = fs::remove_file(&old_path)
+                {
+                    eprintln!(
+                        "Warning: Failed to remove old binary {:?}: {} (run 'axe clean' to retry)",
+                        old_path, e
+                    );
                 }
 
+                // The runtime finds portable data by file name, so it has to follow the binary
+                move_portable_dirs(&old_path, &new_dest);
+
                 // Update lockfile entry
                 let pkg_entry = lockfile.packages.get_mut(&name).unwrap();
 
-                pkg_entry.version = new_version;
+                // A local file may have changed since it was checked, name what was copied
+                pkg_entry.version = match pkg_entry.source {
+                    Source::Local { .. } => local_version(&downloaded.hash),
+                    _ => new_version,
+                };
                 pkg_entry.url = new_url;
-                pkg_entry.hash = hash;
+                pkg_entry.hash = downloaded.hash;
                 pkg_entry.path = new_dest.clone();
+                pkg_entry.installed_at = Some(config::now());
+                pkg_entry.upstream_file = pkg_entry.url.split('/').next_back().map(String::from);
+                pkg_entry.signer = downloaded.checked;
+                pkg_entry.archive_path = downloaded.archive_path;
+                pkg_entry.archive_hash = downloaded.archive_hash;
+                pkg_entry.arch = Some(arch);
+                // Other architectures are resolved again for the new version
+                pkg_entry.resolutions.clear();
+                cache_binary(paths, &new_dest, &pkg_entry.hash);
+                let source_modified = download::modified_time(Path::new(&pkg_entry.origin()));
+                match &mut pkg_entry.source {
+                    Source::Local { modified } => *modified = source_modified,
+                    Source::Direct(recorded) => *recorded = remote.unwrap_or_default(),
+                    Source::Github { .. } | Source::Artifact { .. } | Source::Page { .. } => {}
+                }
+
+                if let Err(e) = link_binary(&name, &new_dest, paths) {
+                    eprintln!("Warning: Failed to link binary: {}", e);
+                }
 
                 // Update desktop file if it exists
-                if pkg_entry.desktop_file.is_some() {
-                    let _ = create_desktop_file(&name, &new_dest, paths);
+                if pkg_entry.desktop_file.is_some()
+                    && let Err(e) = create_desktop_file(pkg_entry, paths)
+                {
+                    eprintln!("Warning: Failed to update desktop file: {}", e);
+                }
+
+                // Keep the persistent extracted copy in sync with the new binary
+                if pkg_entry.run_mode == RunMode::Extracted
+                    && let Err(e) = launch::extract(pkg_entry, paths)
+                {
+                    eprintln!("Warning: {}", e);
                 }
 
                 println!("Successfully updated {}!", name);
             }
             Err(e) => {
                 eprintln!("Failed to update {}: {}", name, e);
+                failed = true;
             }
         }
     }
@@ -460,6 +1450,10 @@ pub async fn handle_update(args: UpdateArgs, paths: &AxePaths) {
     paths
         .save_lockfile(&lockfile)
         .expect("Failed to save lockfile");
+
+    if failed {
+        std::process::exit(1);
+    }
 }
 
 pub fn handle_remove(args: RemoveArgs, paths: &AxePaths) {
@@ -503,13 +1497,1428 @@ pub fn handle_remove(args: RemoveArgs, paths: &AxePaths) {
         let _ = fs::remove_file(&pkg.path);
     }
 
+    unlink_binary(&pkg.name, paths);
+
+    if let Err(e) = launch::remove_extracted(&pkg.name, paths) {
+        eprintln!("Warning: {}", e);
+    }
+
     // Remove desktop file
     if let Some(desktop_path) = pkg.desktop_file.as_ref().filter(|p| p.exists()) {
         let _ = fs::remove_file(desktop_path);
     }
 
+    let data_dirs: Vec<PathBuf> = pkg
+        .portable_dirs()
+        .into_iter()
+        .filter(|d| d.is_dir())
+        .collect();
+    if !data_dirs.is_empty() {
+        let should_purge = if args.purge {
+            true
+        } else if args.yes {
+            false
+        } else {
+            print!("Also delete the portable data of '{}'? [y/N]: ", pkg.name);
+            io::stdout().flush().unwrap();
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).unwrap();
+            let input = input.trim().to_lowercase();
+            input == "y" || input == "yes"
+        };
+
+        if should_purge {
+            for dir in &data_dirs {
+                if let Err(e) = fs::remove_dir_all(dir) {
+                    eprintln!("Warning: Failed to remove {:?}: {}", dir, e);
+                }
+            }
+        } else {
+            println!("Kept the portable data of '{}'.", pkg.name);
+        }
+    }
+
     paths
         .save_lockfile(&lockfile)
         .expect("Failed to save lockfile");
     println!("Successfully removed '{}'!", args.name);
 }
+
+pub async fn handle_adopt(args: AdoptArgs, paths: &AxePaths) {
+    let dir = match args.dir {
+        Some(d) => d,
+        None => match directories::UserDirs::new() {
+            Some(dirs) => dirs.home_dir().join("Applications"),
+            None => {
+                eprintln!("Could not determine home directory. Pass a directory to scan.");
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let mut candidates: Vec<_> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                p.is_file()
+                    && p.extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("appimage"))
+            })
+            .collect(),
+        Err(e) => {
+            eprintln!("Failed to read {:?}: {}", dir, e);
+            std::process::exit(1);
+        }
+    };
+    candidates.sort();
+
+    if candidates.is_empty() {
+        println!("No AppImages found in {:?}.", dir);
+        return;
+    }
+
+    let mut lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let mut adopted = 0;
+
+    for path in candidates {
+        let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+
+        let hash = match download::calculate_hash(&path) {
+            Ok(h) => h,
+            Err(e) => {
+                eprintln!("Skipping {}: failed to calculate hash: {}", file_name, e);
+                continue;
+            }
+        };
+
+        if let Some(existing) = lockfile.packages.values().find(|p| p.hash == hash) {
+            println!(
+                "Skipping {}: already managed as '{}'.",
+                file_name, existing.name
+            );
+            continue;
+        }
+
+        let desktop = appimage::desktop_file(&path).ok().flatten();
+        let name = desktop
+            .as_deref()
+            .and_then(|d| appimage::desktop_value(d, "Name"))
+            .unwrap_or_else(|| {
+                file_name
+                    .trim_end_matches(".AppImage")
+                    .trim_end_matches(".appimage")
+                    .to_string()
+            });
+        let version = desktop
+            .as_deref()
+            .and_then(|d| appimage::desktop_value(d, "X-AppImage-Version"))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        if let Err(e) = config::validate_name(&name) {
+            println!("Skipping {}: {}", file_name, e);
+            continue;
+        }
+        if lockfile.packages.contains_key(&name) {
+            println!("Skipping {}: package '{}' already exists.", file_name, name);
+            continue;
+        }
+
+        println!("Found {} ({} {})", file_name, name, version);
+
+        // Updates should keep the architecture of the file that was adopted
+        let arch = appimage::architecture(&path)
+            .ok()
+            .filter(|a| *a != "unknown")
+            .unwrap_or_else(host::arch);
+
+        let inferred = match appimage::update_information(&path) {
+            Some(info) => source_from_update_info(&info, paths, arch).await,
+            None => None,
+        };
+
+        let (source, url) = match inferred {
+            Some(s) => s,
+            None if args.yes => {
+                println!("Skipping {}: could not determine its source.", file_name);
+                continue;
+            }
+            None => match prompt_adopt_source(&name, paths, arch).await {
+                Some(s) => s,
+                None => {
+                    println!("Skipping {}.", file_name);
+                    continue;
+                }
+            },
+        };
+
+        // The URL has to point at the adopted file's release, not the latest one
+        let (url, version) = match &source {
+            Source::Github {
+                owner,
+                repo,
+                asset_pattern,
+                ..
+            } => match adopted_release_url(owner, repo, &version, asset_pattern, arch).await {
+                Some((tag, release_url)) => (release_url, tag),
+                None => {
+                    println!(
+                        "No release of {}/{} matches version {}, recording it as unknown so 'axe update' replaces it.",
+                        owner, repo, version
+                    );
+                    (url, "unknown".to_string())
+                }
+            },
+            _ => (url, version),
+        };
+
+        let dest = paths.binary_path(&name);
+        if dest.exists() {
+            println!("Skipping {}: {:?} already exists.", file_name, dest);
+            continue;
+        }
+
+        let moved = if args.link {
+            symlink(&path, &dest)
+        } else {
+            fs::rename(&path, &dest).or_else(|_| {
+                // Fall back to copying when the directory is on another filesystem
+                fs::copy(&path, &dest).and_then(|_| fs::remove_file(&path))
+            })
+        };
+        if let Err(e) = moved {
+            eprintln!(
+                "Failed to move {} into {:?}: {}",
+                file_name, paths.bin_dir, e
+            );
+            continue;
+        }
+        if let Err(e) = download::set_executable(&dest) {
+            eprintln!("Warning: {}", e);
+        }
+
+        if let Err(e) = link_binary(&name, &dest, paths) {
+            eprintln!("Warning: Failed to link binary: {}", e);
+        }
+
+        let mut entry = PackageEntry {
+            upstream_file: Some(file_name),
+            arch: Some(arch.to_string()),
+            ..PackageEntry::new(name.clone(), version, url, hash, dest, source)
+        };
+        if args.desktop {
+            match create_desktop_file(&entry, paths) {
+                Ok(p) => entry.desktop_file = Some(p),
+                Err(e) => eprintln!("Warning: Failed to create desktop file: {}", e),
+            }
+        }
+
+        lockfile.packages.insert(name.clone(), entry);
+        adopted += 1;
+        println!("Adopted {}!", name);
+    }
+
+    paths
+        .save_lockfile(&lockfile)
+        .expect("Failed to save lockfile");
+    println!("Adopted {} AppImage(s).", adopted);
+}
+
+/// Tag and download URL of the release an adopted AppImage came from, tagged `version` or
+/// `v<version>`
+async fn adopted_release_url(
+    owner: &str,
+    repo: &str,
+    version: &str,
+    asset_pattern: &Option<String>,
+    arch: &str,
+) -> Option<(String, String)> {
+    if version == "unknown" {
+        return None;
+    }
+    for tag in [version.to_string(), format!("v{}", version)] {
+        if let Ok(meta) =
+            github::find_release_asset(owner, repo, &tag, asset_pattern.as_deref(), arch).await
+        {
+            return Some((meta.version, meta.asset.browser_download_url));
+        }
+    }
+    None
+}
+
+/// Infers the package source from AppImage update information
+async fn source_from_update_info(
+    info: &str,
+    paths: &AxePaths,
+    arch: &str,
+) -> Option<(Source, String)> {
+    let parts: Vec<&str> = info.split('|').collect();
+    match parts.as_slice() {
+        ["gh-releases-zsync", owner, repo, ..] => {
+            resolve_adopt_source(
+                CliSource::Github {
+                    owner: owner.to_string(),
+                    repo: repo.to_string(),
+                },
+                paths,
+                arch,
+            )
+            .await
+        }
+        // The zsync file is published next to the AppImage itself
+        ["zsync", url] => Some((
+            Source::Direct(RemoteFile::default()),
+            url.trim_end_matches(".zsync").to_string(),
+        )),
+        _ => None,
+    }
+}
+
+async fn resolve_adopt_source(
+    source: CliSource,
+    paths: &AxePaths,
+    arch: &str,
+) -> Option<(Source, String)> {
+    let resolved = match resolve_source(source, paths).await {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("Warning: {}", e);
+            return None;
+        }
+    };
+    let (prerelease, asset_pattern) = resolved
+        .index_entry
+        .map(|e| (e.prerelease, e.asset_pattern))
+        .unwrap_or_default();
+
+    match resolved.source {
+        CliSource::Github { owner, repo } => {
+            let url = match github::find_github_asset(
+                &owner,
+                &repo,
+                prerelease,
+                asset_pattern.as_deref(),
+                arch,
+            )
+            .await
+            {
+                Ok(meta) => meta.asset.browser_download_url,
+                Err(e) => {
+                    eprintln!("Warning: Failed to look up {}/{}: {}", owner, repo, e);
+                    return None;
+                }
+            };
+            Some((
+                Source::Github {
+                    owner,
+                    repo,
+                    prerelease,
+                    asset_pattern,
+                },
+                url,
+            ))
+        }
+        CliSource::Url(url) => Some((Source::Direct(RemoteFile::default()), url)),
+        CliSource::Local(path) => match fs::canonicalize(&path) {
+            Ok(path) => Some((
+                Source::Local {
+                    modified: download::modified_time(&path),
+                },
+                config::file_url(&path),
+            )),
+            Err(e) => {
+                eprintln!("Warning: Cannot read {:?}: {}", path, e);
+                None
+            }
+        },
+        CliSource::Catalog(_)
+        | CliSource::Index(_)
+        | CliSource::Page { .. }
+        | CliSource::Artifact { .. } => None,
+    }
+}
+
+async fn prompt_adopt_source(name: &str, paths: &AxePaths, arch: &str) -> Option<(Source, String)> {
+    loop {
+        print!("Source for {} ('owner/repo' or URL, empty to skip): ", name);
+        io::stdout().flush().unwrap();
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+
+        match input.parse::<CliSource>() {
+            Ok(source) => return resolve_adopt_source(source, paths, arch).await,
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+pub fn handle_clean(args: CleanArgs, paths: &AxePaths) {
+    let lockfile = match paths.load_lockfile() {
+        Ok(l) => l,
+        Err(e) => {
+            // Without a readable lockfile every file would look orphaned
+            eprintln!("Failed to load lockfile, refusing to clean: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let referenced: Vec<&Path> = lockfile
+        .packages
+        .values()
+        .flat_map(|p| std::iter::once(p.path.as_path()).chain(p.desktop_file.as_deref()))
+        .collect();
+
+    let portable: Vec<PathBuf> = lockfile
+        .packages
+        .values()
+        .flat_map(|p| p.portable_dirs())
+        .collect();
+
+    let mut orphans: Vec<(PathBuf, u64)> = Vec::new();
+
+    for entry in fs::read_dir(&paths.bin_dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            // Portable data left behind by removed or renamed packages
+            if (name.ends_with(".home") || name.ends_with(".config")) && !portable.contains(&path) {
+                orphans.push((path.clone(), dir_size(&path)));
+            }
+        } else if !referenced.contains(&path.as_path())
+            // Downloads in progress, e.g. of an update running at the same time
+            && !name.ends_with(".part")
+            && !name.ends_with(".archive")
+        {
+            orphans.push((path, meta.len()));
+        }
+    }
+
+    for entry in fs::read_dir(&paths.applications_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "desktop")
+            || referenced.contains(&path.as_path())
+        {
+            continue;
+        }
+
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let generated_by_axe = appimage::desktop_value(&content, DESKTOP_MARKER).is_some()
+            || appimage::desktop_value(&content, "Exec")
+                .is_some_and(|exec| Path::new(&exec).starts_with(&paths.bin_dir));
+
+        if generated_by_axe {
+            orphans.push((path, content.len() as u64));
+        }
+    }
+
+    if orphans.is_empty() {
+        println!("Nothing to clean.");
+        return;
+    }
+
+    let total: u64 = orphans.iter().map(|(_, size)| size).sum();
+    println!("Files not referenced by any package:");
+    for (path, size) in &orphans {
+        println!("  {:<60} {}", path.to_string_lossy(), HumanBytes(*size));
+    }
+    println!("Total: {}", HumanBytes(total));
+
+    if args.dry_run {
+        return;
+    }
+
+    let should_clean = if args.yes {
+        true
+    } else {
+        print!("Delete {} file(s)? [y/N]: ", orphans.len());
+        io::stdout().flush().unwrap();
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let input = input.trim().to_lowercase();
+        input == "y" || input == "yes"
+    };
+
+    if !should_clean {
+        println!("Aborted.");
+        return;
+    }
+
+    let mut freed = 0;
+    for (path, size) in orphans {
+        let result = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        match result {
+            Ok(_) => freed += size,
+            Err(e) => eprintln!("Failed to remove {:?}: {}", path, e),
+        }
+    }
+    println!("Freed {}.", HumanBytes(freed));
+}
+
+pub async fn handle_doctor(paths: &AxePaths) {
+    let checks = doctor::run_checks(paths).await;
+    let mut failed = 0;
+
+    for check in &checks {
+        let label = match check.status {
+            Status::Ok => "[ok]  ",
+            Status::Warn => "[warn]",
+            Status::Fail => {
+                failed += 1;
+                "[fail]"
+            }
+        };
+        println!("{} {}: {}", label, check.name, check.detail);
+        if let Some(fix) = &check.fix {
+            println!("       fix: {}", fix);
+        }
+    }
+
+    if failed > 0 {
+        println!("\n{} check(s) failed.", failed);
+        std::process::exit(1);
+    }
+    println!("\nNo problems found.");
+}
+
+pub fn handle_set_run_mode(args: SetRunModeArgs, paths: &AxePaths) {
+    let mut lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+
+    let pkg = find_package_mut(&mut lockfile, &args.name);
+
+    pkg.run_mode = args.mode;
+
+    let result = if args.mode == RunMode::Extracted {
+        println!("Extracting {}...", pkg.name);
+        launch::extract(pkg, paths).map(|_| ())
+    } else {
+        launch::remove_extracted(&pkg.name, paths)
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    refresh_desktop_file(pkg, paths);
+
+    let name = pkg.name.clone();
+    paths
+        .save_lockfile(&lockfile)
+        .expect("Failed to save lockfile");
+    println!(
+        "{} will now run in '{}' mode.",
+        name,
+        args.mode.to_possible_value().unwrap().get_name()
+    );
+}
+
+pub fn handle_sandbox(args: SandboxArgs, paths: &AxePaths) {
+    let mut lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+
+    let pkg = find_package_mut(&mut lockfile, &args.name);
+
+    pkg.sandbox = if args.disable {
+        None
+    } else {
+        Some(SandboxProfile {
+            backend: args.backend,
+            network: !args.no_network,
+            home: args.home,
+            paths: args.paths,
+            devices: args.devices,
+            display: !args.no_display,
+        })
+    };
+
+    // Menu launches must go through the same sandbox as 'axe run'
+    refresh_desktop_file(pkg, paths);
+
+    let name = pkg.name.clone();
+    let enabled = pkg.sandbox.is_some();
+    paths
+        .save_lockfile(&lockfile)
+        .expect("Failed to save lockfile");
+
+    if enabled {
+        println!("{} will now run sandboxed.", name);
+    } else {
+        println!("Sandbox disabled for {}.", name);
+    }
+}
+
+pub fn handle_set_env(args: SetEnvArgs, paths: &AxePaths) {
+    let mut lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let pkg = find_package_mut(&mut lockfile, &args.name);
+
+    let changed = args.clear || !args.vars.is_empty() || !args.unset.is_empty();
+    if !changed {
+        if pkg.env.is_empty() {
+            println!("No environment variables set for {}.", pkg.name);
+        }
+        for (key, value) in &pkg.env {
+            println!("{}={}", key, value);
+        }
+        return;
+    }
+
+    if args.clear {
+        pkg.env.clear();
+    }
+    for key in &args.unset {
+        pkg.env.remove(key);
+    }
+    for var in args.vars {
+        match var.split_once('=') {
+            Some((key, value)) if !key.is_empty() => {
+                pkg.env.insert(key.to_string(), value.to_string());
+            }
+            _ => {
+                eprintln!("Invalid variable '{}'. Use KEY=VALUE.", var);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    refresh_desktop_file(pkg, paths);
+
+    let name = pkg.name.clone();
+    paths
+        .save_lockfile(&lockfile)
+        .expect("Failed to save lockfile");
+    println!("Updated environment for {}.", name);
+}
+
+pub fn handle_set_args(args: SetArgsArgs, paths: &AxePaths) {
+    let mut lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let pkg = find_package_mut(&mut lockfile, &args.name);
+
+    let changed = args.clear || args.no_cwd || args.cwd.is_some() || !args.args.is_empty();
+    if !changed {
+        println!("Arguments: {}", pkg.args.join(" "));
+        match &pkg.working_dir {
+            Some(dir) => println!("Working directory: {}", dir.to_string_lossy()),
+            None => println!("Working directory: (inherited)"),
+        }
+        return;
+    }
+
+    if args.clear || !args.args.is_empty() {
+        pkg.args = args.args;
+    }
+    if args.no_cwd {
+        pkg.working_dir = None;
+    }
+    if let Some(dir) = args.cwd {
+        let dir = match dir.canonicalize() {
+            Ok(d) if d.is_dir() => d,
+            _ => {
+                eprintln!("{:?} is not a directory.", dir);
+                std::process::exit(1);
+            }
+        };
+        pkg.working_dir = Some(dir);
+    }
+
+    refresh_desktop_file(pkg, paths);
+
+    let name = pkg.name.clone();
+    paths
+        .save_lockfile(&lockfile)
+        .expect("Failed to save lockfile");
+    println!("Updated default arguments for {}.", name);
+}
+
+pub fn handle_set_portable(args: SetPortableArgs, paths: &AxePaths) {
+    let mut lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let pkg = find_package_mut(&mut lockfile, &args.name);
+
+    if args.off {
+        // The runtime uses the dirs whenever they exist, so they have to go
+        for dir in pkg.portable_dirs().iter().filter(|d| d.is_dir()) {
+            if fs::remove_dir(dir).is_err() {
+                eprintln!(
+                    "{:?} is not empty. Back it up or wipe it with 'axe data {} --backup <file>' or '--wipe' first.",
+                    dir, pkg.name
+                );
+                std::process::exit(1);
+            }
+        }
+        pkg.portable = false;
+    } else {
+        if let Err(e) = create_portable_dirs(pkg) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        pkg.portable = true;
+    }
+
+    let name = pkg.name.clone();
+    let portable = pkg.portable;
+    paths
+        .save_lockfile(&lockfile)
+        .expect("Failed to save lockfile");
+
+    if portable {
+        println!("{} now keeps its data next to the binary.", name);
+    } else {
+        println!("Portable mode disabled for {}.", name);
+    }
+}
+
+pub fn handle_data(args: DataArgs, paths: &AxePaths) {
+    let lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let pkg = match lockfile
+        .packages
+        .values()
+        .find(|p| p.name.to_lowercase() == args.name.to_lowercase())
+    {
+        Some(p) => p,
+        None => {
+            eprintln!("Package '{}' not found in lockfile.", args.name);
+            std::process::exit(1);
+        }
+    };
+
+    let dirs: Vec<PathBuf> = pkg
+        .portable_dirs()
+        .into_iter()
+        .filter(|d| d.is_dir())
+        .collect();
+
+    if dirs.is_empty() {
+        println!(
+            "{} has no portable data. Enable it with 'axe set-portable {}'.",
+            pkg.name, pkg.name
+        );
+        return;
+    }
+
+    if let Some(backup) = args.backup {
+        if let Err(e) = archive::create_tar_gz(&backup, &dirs) {
+            eprintln!("Failed to back up portable data of {}: {}", pkg.name, e);
+            std::process::exit(1);
+        }
+        println!("Backed up portable data of {} to {:?}.", pkg.name, backup);
+        return;
+    }
+
+    if args.wipe {
+        let should_wipe = if args.yes {
+            true
+        } else {
+            print!(
+                "Delete all portable data of '{}'? This cannot be undone. [y/N]: ",
+                pkg.name
+            );
+            io::stdout().flush().unwrap();
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).unwrap();
+            let input = input.trim().to_lowercase();
+            input == "y" || input == "yes"
+        };
+
+        if !should_wipe {
+            println!("Aborted.");
+            return;
+        }
+
+        for dir in &dirs {
+            // Recreate the dirs empty so the package stays portable
+            let result = fs::remove_dir_all(dir).and_then(|_| {
+                if pkg.portable {
+                    fs::create_dir(dir)
+                } else {
+                    Ok(())
+                }
+            });
+            if let Err(e) = result {
+                eprintln!("Failed to wipe {:?}: {}", dir, e);
+                std::process::exit(1);
+            }
+        }
+        println!("Wiped portable data of {}.", pkg.name);
+        return;
+    }
+
+    println!("Portable data of {}:", pkg.name);
+    for dir in &dirs {
+        println!(
+            "  {:<60} {}",
+            dir.to_string_lossy(),
+            HumanBytes(dir_size(dir))
+        );
+    }
+}
+
+pub fn handle_verify(paths: &AxePaths, format: OutputFormat) {
+    let lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let mut packages: Vec<_> = lockfile.packages.into_values().collect();
+    packages.sort_by_key(|p| p.name.to_lowercase());
+
+    let records: Vec<VerifyRecord> = packages
+        .into_iter()
+        .map(|pkg| {
+            let actual_hash = download::calculate_hash(&pkg.path).ok();
+            let status = match &actual_hash {
+                None => "missing",
+                Some(h) if *h == pkg.hash => "ok",
+                Some(_) => "modified",
+            };
+            VerifyRecord {
+                name: pkg.name,
+                path: pkg.path,
+                status: status.to_string(),
+                expected_hash: pkg.hash,
+                actual_hash,
+            }
+        })
+        .collect();
+
+    let all_ok = records.iter().all(|r| r.status == "ok");
+
+    if format == OutputFormat::Text {
+        if records.is_empty() {
+            println!("No packages tracked in lockfile.");
+        }
+        for record in &records {
+            println!("{:<30} {}", record.name, record.status);
+        }
+    } else {
+        output::print(format, records);
+    }
+
+    if !all_ok {
+        std::process::exit(1);
+    }
+}
+
+/// Exit code of `axe outdated` when at least one update is available
+const EXIT_UPDATES_AVAILABLE: i32 = 100;
+
+/// Exit code of `axe outdated` when a package could not be checked, even if others have updates
+const EXIT_CHECK_FAILED: i32 = 1;
+
+/// Checks all packages for updates, each for the architecture it was installed for unless
+/// `arch` is given
+pub async fn handle_outdated(
+    paths: &AxePaths,
+    format: OutputFormat,
+    arch: Option<&str>,
+    offline: bool,
+) {
+    let lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let mut packages: Vec<_> = lockfile.packages.into_values().collect();
+    packages.sort_by_key(|p| p.name.to_lowercase());
+
+    let checks = packages.into_iter().map(|pkg| async move {
+        let arch = arch.or(pkg.arch.as_deref()).unwrap_or(host::arch());
+        let (status, available_version, error) = match &pkg.source {
+            _ if offline && !matches!(pkg.source, Source::Local { .. }) => {
+                ("error", None, Some(OFFLINE.to_string()))
+            }
+            Source::Github {
+                owner,
+                repo,
+                prerelease,
+                asset_pattern,
+            } => match github::find_github_asset(
+                owner,
+                repo,
+                *prerelease,
+                asset_pattern.as_deref(),
+                arch,
+            )
+            .await
+            {
+                Ok(meta) if meta.version != pkg.version => ("outdated", Some(meta.version), None),
+                Ok(meta) => ("up-to-date", Some(meta.version), None),
+                Err(e) => ("error", None, Some(e)),
+            },
+            Source::Direct(recorded) => match download::probe(&pkg.url).await {
+                Ok(remote) => {
+                    let version = remote.version_hint(&pkg.url);
+                    // Nothing recorded to compare with: update downloads it again
+                    match recorded.changed(&remote) {
+                        Some(false) => ("up-to-date", version, None),
+                        Some(true) | None => ("outdated", version, None),
+                    }
+                }
+                Err(e) => ("error", None, Some(e)),
+            },
+            Source::Artifact {
+                owner,
+                repo,
+                branch,
+                workflow,
+            } => {
+                match github::find_workflow_artifact(owner, repo, branch, workflow.as_deref(), arch)
+                    .await
+                {
+                    Ok(artifact) if artifact.run_id.to_string() != pkg.version => {
+                        ("outdated", Some(artifact.run_id.to_string()), None)
+                    }
+                    Ok(artifact) => ("up-to-date", Some(artifact.run_id.to_string()), None),
+                    Err(e) => ("error", None, Some(e)),
+                }
+            }
+            Source::Page { page, pattern } => match page::find_latest(page, pattern).await {
+                Ok(link) => {
+                    let version = page_link_version(&link);
+                    let status =
+                        if page::compare_versions(&version, &pkg.version) == Ordering::Greater {
+                            "outdated"
+                        } else {
+                            "up-to-date"
+                        };
+                    (status, Some(version), None)
+                }
+                Err(e) => ("error", None, Some(e)),
+            },
+            Source::Local { modified } => match check_local_source(&pkg, *modified) {
+                Ok(Some(version)) => ("outdated", Some(version), None),
+                Ok(None) => ("up-to-date", Some(pkg.version.clone()), None),
+                Err(e) => ("error", None, Some(e)),
+            },
+        };
+
+        OutdatedRecord {
+            origin: pkg.origin(),
+            name: pkg.name,
+            installed_version: pkg.version,
+            available_version,
+            status: status.to_string(),
+            error,
+        }
+    });
+    let records = futures_util::future::join_all(checks).await;
+
+    let updates = records.iter().filter(|r| r.status == "outdated").count();
+    let failed = records.iter().any(|r| r.status == "error");
+
+    if format == OutputFormat::Text {
+        let outdated: Vec<_> = records.iter().filter(|r| r.status == "outdated").collect();
+        if !outdated.is_empty() {
+            println!(
+                "{:<30} {:<20} {:<20} SOURCE",
+                "NAME", "INSTALLED", "AVAILABLE"
+            );
+            println!("{}", "-".repeat(90));
+        }
+        for record in &outdated {
+            println!(
+                "{:<30} {:<20} {:<20} {}",
+                record.name,
+                record.installed_version,
+                record.available_version.as_deref().unwrap_or("unknown"),
+                record.origin
+            );
+        }
+        for record in records.iter().filter(|r| r.status == "error") {
+            eprintln!(
+                "Failed to check updates for {}: {}",
+                record.name,
+                record.error.as_deref().unwrap_or_default()
+            );
+        }
+        if updates == 0 && !failed {
+            println!("All packages are up to date.");
+        }
+    } else {
+        output::print(format, records);
+    }
+
+    if failed {
+        std::process::exit(EXIT_CHECK_FAILED);
+    }
+    if updates > 0 {
+        std::process::exit(EXIT_UPDATES_AVAILABLE);
+    }
+}
+
+pub fn handle_info(args: InfoArgs, paths: &AxePaths, format: OutputFormat) {
+    let lockfile = paths.load_lockfile().expect("Failed to load lockfile");
+    let pkg = match lockfile
+        .packages
+        .into_values()
+        .find(|p| p.name.to_lowercase() == args.name.to_lowercase())
+    {
+        Some(p) => p,
+        None => {
+            eprintln!("Package '{}' not found in lockfile.", args.name);
+            std::process::exit(1);
+        }
+    };
+
+    let metadata = fs::metadata(&pkg.path).ok();
+    let size = metadata.as_ref().map(|m| m.len());
+    // Entries from older versions of axe have no install time, the file's is close enough
+    let installed_at = pkg.installed_at.or_else(|| {
+        metadata
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+    });
+    let appimage = pkg.path.exists().then(|| {
+        // Read from the SquashFS payload, info never runs the AppImage
+        let desktop = appimage::desktop_file(&pkg.path).ok().flatten();
+        let value = |key| {
+            desktop
+                .as_deref()
+                .and_then(|d| appimage::desktop_value(d, key))
+        };
+        AppImageRecord {
+            name: value("Name"),
+            description: value("Comment"),
+            appimage_type: appimage::appimage_type(&pkg.path),
+            architecture: appimage::architecture(&pkg.path).ok().map(String::from),
+            update_information: appimage::update_information(&pkg.path),
+        }
+    });
+
+    let record = InfoRecord {
+        source: pkg.source.kind().to_string(),
+        origin: pkg.origin(),
+        installed: pkg.path.exists(),
+        name: pkg.name,
+        version: pkg.version,
+        url: pkg.url,
+        hash: pkg.hash,
+        path: pkg.path,
+        size,
+        installed_at,
+        desktop_file: pkg.desktop_file,
+        catalog_id: pkg.catalog_id,
+        signer: pkg.signer,
+        appimage,
+    };
+
+    if format != OutputFormat::Text {
+        output::print(format, vec![record]);
+        return;
+    }
+
+    let none = || "-".to_string();
+    let rows = [
+        ("Name", record.name.clone()),
+        ("Version", record.version.clone()),
+        ("Source", format!("{} ({})", record.origin, record.source)),
+        ("Catalog", record.catalog_id.clone().unwrap_or_else(none)),
+        (
+            "Signed by",
+            record
+                .signer
+                .clone()
+                .unwrap_or_else(|| "not signed".to_string()),
+        ),
+        ("Asset URL", record.url.clone()),
+        ("SHA-256", record.hash.clone()),
+        ("Path", record.path.to_string_lossy().to_string()),
+        (
+            "Size",
+            record
+                .size
+                .map(|s| HumanBytes(s).to_string())
+                .unwrap_or_else(|| "not installed".to_string()),
+        ),
+        (
+            "Installed",
+            record
+                .installed_at
+                .map(format_timestamp)
+                .unwrap_or_else(none),
+        ),
+        (
+            "Desktop entry",
+            record
+                .desktop_file
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(none),
+        ),
+    ];
+    for (label, value) in rows {
+        println!("{:<20} {}", format!("{}:", label), value);
+    }
+
+    if let Some(embedded) = &record.appimage {
+        println!();
+        let rows = [
+            ("Embedded name", embedded.name.clone()),
+            ("Description", embedded.description.clone()),
+            (
+                "AppImage type",
+                embedded.appimage_type.map(|t| t.to_string()),
+            ),
+            ("Architecture", embedded.architecture.clone()),
+            ("Update info", embedded.update_information.clone()),
+        ];
+        for (label, value) in rows {
+            println!(
+                "{:<20} {}",
+                format!("{}:", label),
+                value.unwrap_or_else(none)
+            );
+        }
+    }
+}
+
+/// Formats Unix time as `YYYY-MM-DD HH:MM UTC`
+fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let (hour, minute) = ((secs % 86400) / 3600, (secs % 3600) / 60);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year, month, day, hour, minute
+    )
+}
+
+struct SearchCandidate {
+    name: String,
+    /// Set for catalog results so the catalog id is recorded on add
+    catalog_id: Option<String>,
+    description: String,
+    source: String,
+    version: Option<String>,
+}
+
+pub async fn handle_search(args: SearchArgs, paths: &AxePaths, arch: &str, offline: bool) {
+    if offline && (args.github || args.refresh) {
+        eprintln!("--github and --refresh need the network, which --offline rules out.");
+        std::process::exit(1);
+    }
+
+    let feed = match catalog::load_feed(paths, offline, args.refresh).await {
+        Ok(feed) => feed.items,
+        Err(e) if args.github => {
+            eprintln!("Warning: {}", e);
+            Vec::new()
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut candidates: Vec<SearchCandidate> = feed
+        .iter()
+        .filter(|item| item.matches(&args.term))
+        .filter_map(|item| {
+            Some(SearchCandidate {
+                name: item.name.clone(),
+                catalog_id: Some(item.name.clone()),
+                description: item.description.clone().unwrap_or_default(),
+                source: item.source()?,
+                version: None,
+            })
+        })
+        .take(args.limit)
+        .collect();
+
+    if args.github {
+        match github::search_repositories(&args.term).await {
+            Ok(repos) => {
+                for repo in repos {
+                    if candidates.len() >= args.limit {
+                        break;
+                    }
+                    if candidates
+                        .iter()
+                        .any(|c| c.source.eq_ignore_ascii_case(&repo.full_name))
+                    {
+                        continue;
+                    }
+                    let name = repo
+                        .full_name
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or(&repo.full_name)
+                        .to_string();
+                    candidates.push(SearchCandidate {
+                        name,
+                        catalog_id: None,
+                        description: repo.description.unwrap_or_default(),
+                        source: repo.full_name,
+                        version: None,
+                    });
+                }
+            }
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+
+    // Look up the latest release of GitHub candidates, dropping repos without AppImages.
+    // That is one API request per result, so only --github, which asks for the API anyway,
+    // pays for it.
+    if args.github {
+        let lookups = candidates.iter().map(|c| async move {
+            match c.source.parse::<CliSource>() {
+                Ok(CliSource::Github { owner, repo }) => {
+                    Some(github::find_github_asset(&owner, &repo, false, None, arch).await)
+                }
+                _ => None,
+            }
+        });
+        let versions = futures_util::future::join_all(lookups).await;
+        candidates = candidates
+            .into_iter()
+            .zip(versions)
+            .filter_map(|(mut candidate, version)| match version {
+                Some(Ok(meta)) => {
+                    candidate.version = Some(meta.version);
+                    Some(candidate)
+                }
+                Some(Err(_)) => None,
+                _ => Some(candidate),
+            })
+            .collect();
+    }
+
+    if candidates.is_empty() {
+        println!("No AppImages found for '{}'.", args.term);
+        return;
+    }
+
+    println!(
+        "{:<4} {:<25} {:<15} {:<30} DESCRIPTION",
+        "#", "NAME", "VERSION", "SOURCE"
+    );
+    println!("{}", "-".repeat(110));
+    for (i, candidate) in candidates.iter().enumerate() {
+        let description = candidate
+            .description
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let description: String = description.chars().take(40).collect();
+        println!(
+            "{:<4} {:<25} {:<15} {:<30} {}",
+            i + 1,
+            candidate.name,
+            candidate.version.as_deref().unwrap_or("-"),
+            candidate.source,
+            description
+        );
+    }
+
+    if paths.system && !host::is_root() {
+        println!("Adding a package in system mode requires root, re-run with sudo to pick one.");
+        return;
+    }
+
+    let candidate = loop {
+        print!(
+            "Add which package? [1-{}, empty to cancel]: ",
+            candidates.len()
+        );
+        io::stdout().flush().unwrap();
+        let mut input = String::new();
+        io::stdin().read_line(&mut input).unwrap();
+        let input = input.trim();
+        if input.is_empty() {
+            return;
+        }
+        match input.parse::<usize>() {
+            Ok(n) if (1..=candidates.len()).contains(&n) => break &candidates[n - 1],
+            _ => eprintln!("Please enter a number between 1 and {}", candidates.len()),
+        }
+    };
+
+    let source = match &candidate.catalog_id {
+        Some(id) => CliSource::Catalog(id.clone()),
+        None => match candidate.source.parse::<CliSource>() {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    handle_add(
+        AddArgs {
+            source: Some(source),
+            page: None,
+            pattern: None,
+            branch: None,
+            workflow: None,
+            name: Some(candidate.name.to_lowercase().replace(' ', "-")),
+            prerelease: false,
+            yes: false,
+            desktop: false,
+            portable: false,
+        },
+        paths,
+        arch,
+        offline,
+    )
+    .await;
+}
+
+pub async fn handle_repo(args: RepoArgs, paths: &AxePaths) {
+    let mut config = match paths.load_config() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to load config: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match args.command {
+        RepoCommands::Add { name, url } => {
+            if let Err(e) = config::validate_name(&name) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            if config.indexes.contains_key(&name) {
+                eprintln!("Index '{}' already exists.", name);
+                std::process::exit(1);
+            }
+            // Store local paths absolute so the index works from any directory
+            let location = if url.contains("://") {
+                url
+            } else {
+                match fs::canonicalize(&url) {
+                    Ok(p) => p.to_string_lossy().to_string(),
+                    Err(e) => {
+                        eprintln!("Failed to read index '{}': {}", name, e);
+                        std::process::exit(1);
+                    }
+                }
+            };
+            let index = match index::fetch(paths, &name, &location).await {
+                Ok(index) => index,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            config.indexes.insert(name.clone(), location);
+            paths.save_config(&config).expect("Failed to save config");
+            println!(
+                "Added index '{}' with {} package(s).",
+                name,
+                index.packages.len()
+            );
+        }
+        RepoCommands::List => {
+            if config.indexes.is_empty() {
+                println!("No package indexes configured.");
+                return;
+            }
+            for (name, location) in &config.indexes {
+                println!("{} ({})", name, location);
+                match index::load(paths, name, location).await {
+                    Ok(index) => {
+                        for (package, entry) in &index.packages {
+                            println!(
+                                "  {:<28} {}",
+                                package,
+                                entry.description.as_deref().unwrap_or(&entry.source)
+                            );
+                        }
+                    }
+                    Err(e) => eprintln!("  {}", e),
+                }
+            }
+        }
+        RepoCommands::Remove { name } => {
+            if config.indexes.remove(&name).is_none() {
+                eprintln!("Index '{}' not found.", name);
+                std::process::exit(1);
+            }
+            // Names edited into the config by hand may not be safe to join
+            if config::validate_name(&name).is_ok() {
+                let _ = fs::remove_file(paths.index_cache_path(&name));
+            }
+            paths.save_config(&config).expect("Failed to save config");
+            println!("Removed index '{}'.", name);
+        }
+        RepoCommands::Update => {
+            let mut failed = false;
+            for (name, location) in &config.indexes {
+                match index::fetch(paths, name, location).await {
+                    Ok(index) => {
+                        println!("Updated '{}' ({} package(s)).", name, index.packages.len())
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+pub fn handle_cache(args: CacheArgs, paths: &AxePaths) {
+    let limit = cache::limit(paths);
+    match args.command {
+        CacheCommands::Info => {
+            let entries = cache::entries(paths);
+            let size: u64 = entries.iter().map(|e| e.size).sum();
+            println!("Location:  {:?}", paths.cache_dir());
+            println!("AppImages: {}", entries.len());
+            println!("Size:      {} of {}", HumanBytes(size), HumanBytes(limit));
+        }
+        CacheCommands::Prune { all } => {
+            let lockfile = if all {
+                Lockfile::default()
+            } else {
+                match paths.load_lockfile() {
+                    Ok(l) => l,
+                    Err(e) => {
+                        // Without a readable lockfile every cached copy would look unused
+                        eprintln!("Failed to load lockfile, refusing to prune: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            };
+            // Files resolved for other architectures are referenced too
+            let keep: HashSet<&str> = lockfile
+                .packages
+                .values()
+                .flat_map(|p| {
+                    std::iter::once(p.hash.as_str())
+                        .chain(p.resolutions.values().map(|r| r.hash.as_str()))
+                })
+                .collect();
+            let (removed, freed) = cache::prune(paths, &keep, limit);
+            println!(
+                "Removed {} cached AppImage(s), freed {}.",
+                removed,
+                HumanBytes(freed)
+            );
+        }
+    }
+}
@@ -1,12 +1,39 @@
+use clap::ValueEnum;
 use directories::ProjectDirs;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 pub struct AxePaths {
     pub config_dir: PathBuf,
     pub data_dir: PathBuf,
     pub bin_dir: PathBuf,
     pub applications_dir: PathBuf,
+    /// Directory that gets a symlink to every installed binary (system mode only)
+    pub link_dir: Option<PathBuf>,
+    pub system: bool,
+}
+
+/// User settings read from `config.toml` in the config directory
+#[derive(Serialize, Deserialize, Default)]
+pub struct Config {
+    /// Use the system-wide installation paths by default
+    #[serde(default)]
+    pub system: bool,
+    /// Package indexes by name, each a URL or local path to a TOML or JSON file
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub indexes: BTreeMap<String, String>,
+    /// Armored public key files trusted to sign AppImages, in addition to embedded keys
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_keys: Vec<PathBuf>,
+    /// Size limit of the download cache in MiB, 0 disables it [default: 2048]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_limit_mb: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -21,8 +48,160 @@ pub enum Source {
         owner: String,
         repo: String,
         prerelease: bool,
+        /// Glob selecting the release asset, e.g. `tool-*-x86_64.AppImage`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        asset_pattern: Option<String>,
+    },
+    Direct(RemoteFile),
+    /// Artifact of the latest successful GitHub Actions run on a branch, `version` is the run id
+    Artifact {
+        owner: String,
+        repo: String,
+        branch: String,
+        /// Workflow file the runs must come from, e.g. `nightly.yml`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        workflow: Option<String>,
+    },
+    /// A vendor download page, scraped for the newest link matching `pattern`
+    Page {
+        page: String,
+        /// Regex for the link, optionally capturing the version
+        pattern: String,
     },
-    Direct,
+    /// A file on disk or a network share, `url` is its `file://` URL
+    Local {
+        /// Modification time of the source file when it was last copied
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        modified: Option<u64>,
+    },
+}
+
+/// What the server reported for a direct URL, compared by `axe update` to spot new uploads
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_length: Option<u64>,
+    /// Where the URL redirected to, for "latest" links that point at versioned files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_url: Option<String>,
+}
+
+impl RemoteFile {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether `current` describes a different file, `None` when the server gives nothing to compare
+    pub fn changed(&self, current: &RemoteFile) -> Option<bool> {
+        fn differs<T: PartialEq>(old: &Option<T>, new: &Option<T>) -> Option<bool> {
+            match (old, new) {
+                (Some(old), Some(new)) => Some(old != new),
+                _ => None,
+            }
+        }
+
+        // Signed CDN links carry a fresh query on every request, only the path names the file
+        let without_query = |url: &Option<String>| {
+            url.as_deref()
+                .map(|u| u.split(['?', '#']).next().unwrap_or(u).to_string())
+        };
+        if differs(
+            &without_query(&self.final_url),
+            &without_query(&current.final_url),
+        ) == Some(true)
+        {
+            return Some(true);
+        }
+        [
+            differs(&self.etag, &current.etag),
+            differs(&self.last_modified, &current.last_modified),
+            differs(&self.content_length, &current.content_length),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(|a, b| a || b)
+    }
+
+    /// File name of the redirect target, used as the version of "latest" links
+    pub fn version_hint(&self, url: &str) -> Option<String> {
+        let final_url = self.final_url.as_deref().filter(|f| *f != url)?;
+        let path = final_url.split(['?', '#']).next().unwrap_or(final_url);
+        path.rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+    }
+}
+
+/// How `axe run` starts a package
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RunMode {
+    /// Mount with FUSE when available, otherwise extract and run
+    #[default]
+    Auto,
+    /// Always mount the AppImage with FUSE
+    Fuse,
+    /// Extract to a temporary directory on every launch
+    ExtractAndRun,
+    /// Keep a persistent extracted copy under the data dir
+    Extracted,
+}
+
+impl RunMode {
+    pub fn is_auto(&self) -> bool {
+        *self == RunMode::Auto
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum SandboxBackend {
+    /// Use bubblewrap if installed, otherwise firejail
+    #[default]
+    Auto,
+    Bubblewrap,
+    Firejail,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum HomeAccess {
+    /// Start with an empty home directory
+    #[default]
+    None,
+    /// Expose the real home directory read-only
+    ReadOnly,
+}
+
+/// Confinement applied when the package is started through axe
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SandboxProfile {
+    #[serde(default)]
+    pub backend: SandboxBackend,
+    pub network: bool,
+    pub home: HomeAccess,
+    /// Extra paths exposed read-write, e.g. `~/Documents`
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    /// Access to the GPU and other devices in /dev
+    pub devices: bool,
+    /// Access to the X11 and Wayland sockets
+    pub display: bool,
+}
+
+/// The file a package version was resolved to for one architecture
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Resolution {
+    pub version: String,
+    pub url: String,
+    pub hash: String,
+    pub archive_path: Option<String>,
+    pub archive_hash: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -33,10 +212,183 @@ pub struct PackageEntry {
     pub hash: String,
     pub path: PathBuf,
     pub desktop_file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "RunMode::is_auto")]
+    pub run_mode: RunMode,
+    pub sandbox: Option<SandboxProfile>,
+    /// Environment variables set when the package is started
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Arguments passed before any given on the command line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    pub working_dir: Option<PathBuf>,
+    /// Keep the app's home and config in `.home`/`.config` dirs next to the binary
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub portable: bool,
+    /// File name of the release asset, the binary itself is stored as `<name>.AppImage`
+    pub upstream_file: Option<String>,
+    /// Unix time the current version was installed
+    pub installed_at: Option<u64>,
+    /// App name in the AppImageHub catalog the source was resolved from
+    pub catalog_id: Option<String>,
+    /// Fingerprint of the key the AppImage was first seen signed with, updates must match it
+    pub signer: Option<String>,
+    /// Architecture the installed AppImage was resolved for
+    pub arch: Option<String>,
+    /// Path of the AppImage inside the archive `url` points to
+    pub archive_path: Option<String>,
+    /// SHA-256 of the archive `url` points to, `hash` is the unpacked AppImage's
+    pub archive_hash: Option<String>,
+    /// Files `axe install` resolved for other architectures than `arch`, keyed by architecture
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub resolutions: BTreeMap<String, Resolution>,
     #[serde(flatten)]
     pub source: Source,
 }
 
+impl Source {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Source::Github { .. } => "github",
+            Source::Direct(_) => "direct",
+            Source::Artifact { .. } => "artifact",
+            Source::Page { .. } => "page",
+            Source::Local { .. } => "local",
+        }
+    }
+}
+
+impl PackageEntry {
+    pub fn new(
+        name: String,
+        version: String,
+        url: String,
+        hash: String,
+        path: PathBuf,
+        source: Source,
+    ) -> Self {
+        Self {
+            name,
+            version,
+            url,
+            hash,
+            path,
+            desktop_file: None,
+            run_mode: RunMode::default(),
+            sandbox: None,
+            env: BTreeMap::new(),
+            args: Vec::new(),
+            working_dir: None,
+            portable: false,
+            upstream_file: None,
+            installed_at: Some(now()),
+            catalog_id: None,
+            signer: None,
+            arch: None,
+            archive_path: None,
+            archive_hash: None,
+            resolutions: BTreeMap::new(),
+            source,
+        }
+    }
+
+    /// Where the package comes from: `owner/repo` for GitHub, `owner/repo@branch` for
+    /// workflow artifacts, the download page for
+    /// scraped links, the path for local files, the URL otherwise
+    pub fn origin(&self) -> String {
+        match &self.source {
+            Source::Github { owner, repo, .. } => format!("{}/{}", owner, repo),
+            Source::Direct(_) => self.url.clone(),
+            Source::Artifact {
+                owner,
+                repo,
+                branch,
+                ..
+            } => format!("{}/{}@{}", owner, repo, branch),
+            Source::Page { page, .. } => page.clone(),
+            Source::Local { .. } => file_path(&self.url)
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|| self.url.trim_start_matches("file://").to_string()),
+        }
+    }
+
+    /// The file recorded for `arch`, from the entry itself or from `resolutions`
+    ///
+    /// Only GitHub releases are resolved per architecture, other sources always point to the
+    /// one file. Returns None when `arch` still has to be resolved for this version.
+    pub fn resolution(&self, arch: &str) -> Option<Resolution> {
+        let own_arch = self.arch.as_deref().is_none_or(|a| a == arch);
+        if own_arch || !matches!(self.source, Source::Github { .. }) {
+            return Some(Resolution {
+                version: self.version.clone(),
+                url: self.url.clone(),
+                hash: self.hash.clone(),
+                archive_path: self.archive_path.clone(),
+                archive_hash: self.archive_hash.clone(),
+            });
+        }
+        self.resolutions
+            .get(arch)
+            .filter(|r| r.version == self.version)
+            .cloned()
+    }
+
+    /// The `<file>.home` and `<file>.config` dirs the AppImage runtime uses in portable mode
+    pub fn portable_dirs(&self) -> [PathBuf; 2] {
+        portable_dirs(&self.path)
+    }
+}
+
+/// Checks that a package or index name can be used as a file name
+///
+/// Names end up in paths under the data dir, so they must not be able to leave it.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Name must not be empty".to_string());
+    }
+    if name.starts_with('.') || name.contains(['/', '\\', '\0']) {
+        return Err(format!(
+            "Invalid name '{}': it must not start with '.' or contain '/', '\\' or NUL",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// `file://` URL of an absolute path, percent-encoded
+pub fn file_url(path: &Path) -> String {
+    Url::from_file_path(path)
+        .map(String::from)
+        .unwrap_or_else(|_| format!("file://{}", path.display()))
+}
+
+/// Path a `file://` URL points to, with percent-encoding decoded
+pub fn file_path(url: &str) -> Option<PathBuf> {
+    Url::parse(url)
+        .ok()
+        .filter(|u| u.scheme() == "file")
+        .and_then(|u| u.to_file_path().ok())
+}
+
+/// Current Unix time in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn portable_dirs(path: &Path) -> [PathBuf; 2] {
+    let file_name = path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    [
+        path.with_file_name(format!("{}.home", file_name)),
+        path.with_file_name(format!("{}.config", file_name)),
+    ]
+}
+
 impl AxePaths {
     pub fn new() -> Result<Self, String> {
         let proj_dirs =
@@ -56,9 +408,23 @@ impl AxePaths {
             data_dir,
             bin_dir,
             applications_dir,
+            link_dir: None,
+            system: false,
         })
     }
 
+    pub fn system() -> Self {
+        let data_dir = PathBuf::from("/opt/axe");
+        Self {
+            config_dir: PathBuf::from("/etc/axe"),
+            bin_dir: data_dir.join("bin"),
+            data_dir,
+            applications_dir: PathBuf::from("/usr/local/share/applications"),
+            link_dir: Some(PathBuf::from("/usr/local/bin")),
+            system: true,
+        }
+    }
+
     pub fn ensure_dirs(&self) -> Result<(), String> {
         fs::create_dir_all(&self.config_dir)
             .map_err(|e| format!("Failed to create config dir: {}", e))?;
@@ -68,9 +434,56 @@ impl AxePaths {
             .map_err(|e| format!("Failed to create bin dir: {}", e))?;
         fs::create_dir_all(&self.applications_dir)
             .map_err(|e| format!("Failed to create applications dir: {}", e))?;
+        if let Some(link_dir) = &self.link_dir {
+            fs::create_dir_all(link_dir)
+                .map_err(|e| format!("Failed to create link dir: {}", e))?;
+        }
         Ok(())
     }
 
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.join("config.toml")
+    }
+
+    pub fn load_config(&self) -> Result<Config, String> {
+        let path = self.config_path();
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        toml::from_str(&content).map_err(|e| e.to_string())
+    }
+
+    pub fn save_config(&self, config: &Config) -> Result<(), String> {
+        let content = toml::to_string_pretty(config).map_err(|e| e.to_string())?;
+        fs::write(self.config_path(), content).map_err(|e| e.to_string())
+    }
+
+    /// Local copy of a package index, refreshed by `axe repo update`
+    pub fn index_cache_path(&self, name: &str) -> PathBuf {
+        self.data_dir.join("indexes").join(name)
+    }
+
+    /// Stable location of a package's binary, independent of the upstream file name
+    pub fn binary_path(&self, name: &str) -> PathBuf {
+        self.bin_dir.join(format!("{}.AppImage", name))
+    }
+
+    /// Downloaded AppImages by SHA-256, used to reinstall without network
+    pub fn cache_dir(&self) -> PathBuf {
+        self.data_dir.join("cache")
+    }
+
+    /// Cached copy of the AppImageHub catalog used for offline searches
+    pub fn catalog_cache_path(&self) -> PathBuf {
+        self.data_dir.join("catalog").join("feed.json")
+    }
+
+    /// Directory holding the persistent extracted copy of a package
+    pub fn extracted_dir(&self, name: &str) -> PathBuf {
+        self.data_dir.join("extracted").join(name)
+    }
+
     pub fn lockfile_path(&self) -> PathBuf {
         self.config_dir.join("axe.lock")
     }
@@ -90,3 +503,59 @@ impl AxePaths {
         fs::write(path, content).map_err(|e| e.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_plain_names() {
+        assert!(validate_name("firefox").is_ok());
+        assert!(validate_name("Some App 2.0").is_ok());
+    }
+
+    #[test]
+    fn rejects_names_leaving_the_dir() {
+        for name in [
+            "",
+            ".",
+            "..",
+            "../bin",
+            "a/b",
+            "/etc/passwd",
+            ".hidden",
+            "a\\b",
+            "a\0b",
+        ] {
+            assert!(validate_name(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn ignores_the_query_of_redirect_targets() {
+        let remote = |url: &str, etag: &str| RemoteFile {
+            etag: Some(etag.to_string()),
+            final_url: Some(url.to_string()),
+            ..RemoteFile::default()
+        };
+        let recorded = remote("https://cdn.example.com/App-1.0.AppImage?sig=a", "\"1\"");
+
+        let resigned = remote("https://cdn.example.com/App-1.0.AppImage?sig=b", "\"1\"");
+        assert_eq!(recorded.changed(&resigned), Some(false));
+
+        let reuploaded = remote("https://cdn.example.com/App-1.0.AppImage?sig=b", "\"2\"");
+        assert_eq!(recorded.changed(&reuploaded), Some(true));
+
+        let released = remote("https://cdn.example.com/App-1.1.AppImage?sig=b", "\"1\"");
+        assert_eq!(recorded.changed(&released), Some(true));
+    }
+
+    #[test]
+    fn round_trips_file_urls() {
+        let path = Path::new("/srv/builds/My App #2.AppImage");
+        let url = file_url(path);
+        assert_eq!(url, "file:///srv/builds/My%20App%20%232.AppImage");
+        assert_eq!(file_path(&url).as_deref(), Some(path));
+        assert_eq!(file_path("https://example.com/App.AppImage"), None);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{
+    config::{AxePaths, Source},
+    github, host,
+};
+use std::{collections::BTreeSet, env, path::Path, time::Duration};
+
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+pub async fn run_checks(paths: &AxePaths) -> Vec<Check> {
+    let mut checks = vec![
+        check_libfuse(),
+        check_dev_fuse(),
+        check_noexec("Temporary directory", &env::temp_dir(), true),
+        check_noexec("Binary directory", &paths.bin_dir, false),
+        check_user_namespaces(),
+        check_data_dirs(paths),
+    ];
+
+    let (lockfile_check, hosts) = check_lockfile(paths);
+    checks.push(lockfile_check);
+    checks.extend(check_hosts(hosts).await);
+    checks.push(check_token().await);
+    checks
+}
+
+fn check_libfuse() -> Check {
+    if host::has_libfuse2() {
+        Check::ok("libfuse2", "libfuse.so.2 is installed")
+    } else {
+        Check::warn(
+            "libfuse2",
+            "libfuse.so.2 was not found, AppImages will be extracted on every launch",
+            "Install it with 'sudo apt install libfuse2' (Debian/Ubuntu), \
+             'sudo dnf install fuse-libs' (Fedora) or 'sudo pacman -S fuse2' (Arch), \
+             or use 'axe set-run-mode <name> extracted' to extract only once",
+        )
+    }
+}
+
+fn check_dev_fuse() -> Check {
+    if Path::new("/dev/fuse").exists() {
+        Check::ok("FUSE device", "/dev/fuse is available")
+    } else {
+        Check::warn(
+            "FUSE device",
+            "/dev/fuse does not exist, AppImages will be extracted on every launch",
+            "Load the kernel module with 'sudo modprobe fuse', pass --device /dev/fuse \
+             when running inside a container, or use 'axe set-run-mode <name> extracted'",
+        )
+    }
+}
+
+fn check_noexec(name: &str, dir: &Path, tmp: bool) -> Check {
+    if !host::is_noexec(dir) {
+        return Check::ok(name, format!("{:?} allows executing files", dir));
+    }
+
+    let fix = if tmp {
+        "Set TMPDIR to a directory on a filesystem mounted without 'noexec', \
+         e.g. 'export TMPDIR=$HOME/.cache/tmp'"
+            .to_string()
+    } else {
+        format!(
+            "Remount the filesystem holding {:?} without 'noexec', \
+             or use --system to install into /opt/axe",
+            dir
+        )
+    };
+    Check::fail(name, format!("{:?} is on a noexec mount", dir), fix)
+}
+
+fn check_user_namespaces() -> Check {
+    if host::user_namespaces_enabled() {
+        Check::ok(
+            "User namespaces",
+            "Unprivileged user namespaces are enabled",
+        )
+    } else {
+        Check::warn(
+            "User namespaces",
+            "Unprivileged user namespaces are disabled, Electron based apps may refuse to start",
+            "Enable them with 'sudo sysctl kernel.unprivileged_userns_clone=1' or \
+             'sudo sysctl kernel.apparmor_restrict_unprivileged_userns=0', \
+             or run the app with --no-sandbox",
+        )
+    }
+}
+
+fn check_data_dirs(paths: &AxePaths) -> Check {
+    let Some(data_dir) = paths.applications_dir.parent() else {
+        return Check::ok("Desktop entries", "Nothing to check");
+    };
+
+    let data_home = env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .or_else(|| env::var("HOME").ok().map(|h| format!("{}/.local/share", h)));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+
+    let visible = data_home
+        .iter()
+        .map(String::as_str)
+        .chain(data_dirs.split(':'))
+        .any(|d| Path::new(d.trim_end_matches('/')) == data_dir);
+
+    if visible {
+        Check::ok(
+            "Desktop entries",
+            format!(
+                "{:?} is searched by desktop environments",
+                paths.applications_dir
+            ),
+        )
+    } else {
+        Check::fail(
+            "Desktop entries",
+            format!(
+                "{:?} is not in XDG_DATA_HOME or XDG_DATA_DIRS, apps won't show up in menus",
+                data_dir
+            ),
+            format!(
+                "Add 'export XDG_DATA_DIRS=\"{}:$XDG_DATA_DIRS\"' to your ~/.profile and log in again",
+                data_dir.to_string_lossy()
+            ),
+        )
+    }
+}
+
+/// Checks that the lockfile parses and returns the hosts its packages download from
+fn check_lockfile(paths: &AxePaths) -> (Check, BTreeSet<String>) {
+    let mut hosts = BTreeSet::from(["api.github.com".to_string()]);
+
+    let check = match paths.load_lockfile() {
+        Ok(lockfile) => {
+            for pkg in lockfile.packages.values() {
+                if let Source::Github { .. } = pkg.source {
+                    hosts.insert("github.com".to_string());
+                }
+                if let Some(host) = reqwest::Url::parse(&pkg.url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(String::from))
+                {
+                    hosts.insert(host);
+                }
+            }
+            Check::ok(
+                "Lockfile",
+                format!(
+                    "{:?} is valid ({} packages)",
+                    paths.lockfile_path(),
+                    lockfile.packages.len()
+                ),
+            )
+        }
+        Err(e) => Check::fail(
+            "Lockfile",
+            format!("{:?} could not be parsed: {}", paths.lockfile_path(), e),
+            "Fix the reported line by hand, or restore the file from your dotfiles",
+        ),
+    };
+
+    (check, hosts)
+}
+
+async fn check_hosts(hosts: BTreeSet<String>) -> Vec<Check> {
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+    {
+        Ok(c) => c,
+        Err(e) => {
+            return vec![Check::fail(
+                "Network",
+                format!("Failed to create HTTP client: {}", e),
+                "Check your TLS setup",
+            )];
+        }
+    };
+
+    let requests = hosts.into_iter().map(|host| {
+        let client = client.clone();
+        async move {
+            let name = format!("Network: {}", host);
+            match client.head(format!("https://{}", host)).send().await {
+                Ok(_) => Check::ok(name, "Reachable"),
+                Err(e) => Check::fail(
+                    name,
+                    format!("Unreachable: {}", e),
+                    "Check your internet connection, DNS and proxy settings (HTTPS_PROXY)",
+                ),
+            }
+        }
+    });
+
+    futures_util::future::join_all(requests).await
+}
+
+async fn check_token() -> Check {
+    let Some(_) = github::token() else {
+        return Check::ok(
+            "GitHub token",
+            "GITHUB_TOKEN is not set, anonymous requests are limited to 60 per hour",
+        );
+    };
+
+    let client = reqwest::Client::new();
+    match github::api_request(&client, "https://api.github.com/user")
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+    {
+        Ok(r) if r.status().is_success() => Check::ok("GitHub token", "GITHUB_TOKEN is valid"),
+        Ok(r) if r.status() == reqwest::StatusCode::UNAUTHORIZED => Check::fail(
+            "GitHub token",
+            "GITHUB_TOKEN was rejected by GitHub",
+            "Create a new token at https://github.com/settings/tokens and update GITHUB_TOKEN",
+        ),
+        Ok(r) => Check::warn(
+            "GitHub token",
+            format!("Unexpected response from GitHub: {}", r.status()),
+            "Try again later",
+        ),
+        Err(e) => Check::warn(
+            "GitHub token",
+            format!("Could not validate GITHUB_TOKEN: {}", e),
+            "Check your network connection",
+        ),
+    }
+}
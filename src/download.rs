@@ -1,18 +1,182 @@
+use crate::{
+    appimage, archive,
+    config::{self, RemoteFile},
+    github,
+};
 use bytes::Bytes;
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::header::{CONTENT_LENGTH, ETAG, HeaderMap, LAST_MODIFIED};
 use sha2::{Digest, Sha256};
 use std::{
     fs,
     io::{Read, Write},
     os::unix::fs::PermissionsExt,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
 };
 
-pub async fn download_file(url: &str, dest: PathBuf, name: &str) -> Result<String, String> {
-    let response = reqwest::get(url)
+pub struct Downloaded<T> {
+    /// SHA-256 of the installed AppImage
+    pub hash: String,
+    /// Path of the AppImage inside the archive, when `url` points to one
+    pub archive_path: Option<String>,
+    /// SHA-256 of the archive itself, when `url` points to one
+    pub archive_hash: Option<String>,
+    /// Result of the caller's check
+    pub checked: T,
+}
+
+/// Downloads the AppImage at `url` to `dest`
+///
+/// The download, i.e. the archive for packed AppImages, is compared with `expected_hash`
+/// when given. A `.tar.gz` or `.zip` is
+/// unpacked, taking the AppImage at `archive_path` or the best match for `arch`. The file
+/// is then checked to be an AppImage for `arch`, and `check` runs on it before it replaces
+/// `dest`; any failure discards it.
+pub async fn download_file<T>(
+    url: &str,
+    dest: PathBuf,
+    name: &str,
+    arch: &str,
+    expected_hash: Option<&str>,
+    archive_path: Option<&str>,
+    check: impl FnOnce(&Path) -> Result<T, String>,
+) -> Result<Downloaded<T>, String> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directories: {}", e))?;
+    }
+
+    // Download next to the destination and swap it in at the end, so a running
+    // AppImage isn't overwritten and interrupted downloads never look installed
+    let part = partial_path(&dest);
+    let hash = if url.starts_with("file://") {
+        match config::file_path(url) {
+            Some(source) => copy_local(&source, &part),
+            None => Err(format!("Invalid file URL '{}'", url)),
+        }
+    } else {
+        fetch(url, &part, name).await
+    };
+    let hash = match hash {
+        Ok(hash) => hash,
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            return Err(e);
+        }
+    };
+
+    if let Some(expected) = expected_hash
+        && !expected.eq_ignore_ascii_case(&hash)
+    {
+        let _ = fs::remove_file(&part);
+        return Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            name, expected, hash
+        ));
+    }
+
+    // Workflow artifacts are served as zips from URLs without an extension
+    let kind = archive::kind(file_name(url)).or_else(|| archive::detect(&part));
+    let (hash, archive_path, archive_hash) = match kind {
+        Some(kind) => {
+            let archive_file = dest.with_file_name(format!("{}.archive", name));
+            fs::rename(&part, &archive_file).map_err(|e| e.to_string())?;
+            let inner = archive::extract_appimage(&archive_file, kind, archive_path, arch, &part);
+            let _ = fs::remove_file(&archive_file);
+            let inner = match inner {
+                Ok(inner) => inner,
+                Err(e) => {
+                    let _ = fs::remove_file(&part);
+                    return Err(e);
+                }
+            };
+            (calculate_hash(&part)?, Some(inner), Some(hash))
+        }
+        None => (hash, None, None),
+    };
+
+    let checked = match appimage::validate(&part, arch).and_then(|_| check(&part)) {
+        Ok(checked) => checked,
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            return Err(e);
+        }
+    };
+
+    // chmod +x
+    set_executable(&part)?;
+    fs::rename(&part, &dest).map_err(|e| format!("Failed to move download into place: {}", e))?;
+
+    Ok(Downloaded {
+        hash,
+        archive_path,
+        archive_hash,
+        checked,
+    })
+}
+
+/// Asks the server what `url` currently points to, following redirects
+///
+/// Uses a HEAD request, falling back to a GET whose body is never read for servers that
+/// refuse HEAD.
+pub async fn probe(url: &str) -> Result<RemoteFile, String> {
+    let client = reqwest::Client::new();
+    let mut response = client
+        .head(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach '{}': {}", url, e))?;
+    if !response.status().is_success() {
+        response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach '{}': {}", url, e))?;
+    }
+    if !response.status().is_success() {
+        return Err(format!("'{}' returned {}", url, response.status()));
+    }
+
+    let headers = response.headers();
+    let header = |headers: &HeaderMap, name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    Ok(RemoteFile {
+        etag: header(headers, ETAG),
+        last_modified: header(headers, LAST_MODIFIED),
+        content_length: header(headers, CONTENT_LENGTH).and_then(|l| l.parse().ok()),
+        final_url: Some(response.url().to_string()),
+    })
+}
+
+/// Last path segment of a URL, without the query string
+fn file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Streams `url` into `part` with a progress bar and returns its SHA-256
+pub async fn fetch(url: &str, part: &Path, name: &str) -> Result<String, String> {
+    let client = reqwest::Client::new();
+    let request = if url.starts_with("https://api.github.com/") {
+        github::api_request(&client, url)
+    } else {
+        client.get(url)
+    };
+    let response = request
+        .send()
         .await
         .map_err(|e| format!("Failed to download: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download: server returned {}",
+            response.status()
+        ));
+    }
 
     let total_size = response
         .content_length()
@@ -25,10 +189,7 @@ pub async fn download_file(url: &str, dest: PathBuf, name: &str) -> Result<Strin
         .progress_chars("#>-"));
     pb.set_message(name.to_string());
 
-    if let Some(parent) = dest.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directories: {}", e))?;
-    }
-    let mut file = fs::File::create(&dest).map_err(|e| format!("Failed to create file: {}", e))?;
+    let mut file = fs::File::create(part).map_err(|e| format!("Failed to create file: {}", e))?;
     let mut downloaded: u64 = 0;
     let mut stream = response.bytes_stream();
     let mut hasher = Sha256::new();
@@ -45,15 +206,22 @@ pub async fn download_file(url: &str, dest: PathBuf, name: &str) -> Result<Strin
     }
 
     pb.finish_with_message(format!("{} downloaded", name));
+    Ok(hex::encode(hasher.finalize()))
+}
 
-    // chmod +x
-    set_executable(&dest)?;
+/// Copies a local file into `part` and returns its SHA-256
+fn copy_local(source: &Path, part: &Path) -> Result<String, String> {
+    fs::copy(source, part).map_err(|e| format!("Failed to copy {:?}: {}", source, e))?;
+    calculate_hash(part)
+}
 
-    let hash = hex::encode(hasher.finalize());
-    Ok(hash)
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
 }
 
-pub fn calculate_hash(path: &PathBuf) -> Result<String, String> {
+pub fn calculate_hash(path: &Path) -> Result<String, String> {
     let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
     let mut hasher = Sha256::new();
     let mut buffer = [0; 1024];
@@ -67,6 +235,15 @@ pub fn calculate_hash(path: &PathBuf) -> Result<String, String> {
     Ok(hex::encode(hasher.finalize()))
 }
 
+/// Modification time of a file in Unix seconds
+pub fn modified_time(path: &Path) -> Option<u64> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+}
+
 pub fn set_executable(path: &PathBuf) -> Result<(), String> {
     let mut perms = fs::metadata(path).map_err(|e| e.to_string())?.permissions();
     perms.set_mode(0o755);
@@ -1,4 +1,5 @@
-use reqwest::header::USER_AGENT;
+use crate::{archive, download, host};
+use reqwest::header::{AUTHORIZATION, USER_AGENT};
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug)]
@@ -12,25 +13,84 @@ struct GithubRelease {
 pub struct GithubAsset {
     pub name: String,
     pub browser_download_url: String,
+    /// Checksum computed by GitHub, e.g. `sha256:<hex>`
+    pub digest: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    items: Vec<SearchRepo>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchRepo {
+    pub full_name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WorkflowRuns {
+    workflow_runs: Vec<WorkflowRun>,
+}
+
+#[derive(Deserialize)]
+struct WorkflowRun {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct Artifacts {
+    artifacts: Vec<Artifact>,
+}
+
+#[derive(Deserialize)]
+struct Artifact {
+    name: String,
+    archive_download_url: String,
+    expired: bool,
+}
+
+/// Artifact of a workflow run, downloadable as a zip
+pub struct ArtifactMetadata {
+    pub run_id: u64,
+    pub name: String,
+    pub url: String,
 }
 
 pub struct RepoMetadata {
     pub asset: GithubAsset,
+    /// Checksum file published in the same release, if any
+    pub checksum_asset: Option<GithubAsset>,
     pub version: String,
 }
 
+/// Personal access token used to raise the API rate limit
+pub fn token() -> Option<String> {
+    std::env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|t| !t.trim().is_empty())
+}
+
+/// Builds a GitHub API request, authenticated when a token is available
+pub fn api_request(client: &reqwest::Client, url: &str) -> reqwest::RequestBuilder {
+    let request = client.get(url).header(USER_AGENT, "axe-package-manager");
+    match token() {
+        Some(token) => request.header(AUTHORIZATION, format!("Bearer {}", token)),
+        None => request,
+    }
+}
+
 pub async fn find_github_asset(
     owner: &str,
     repo: &str,
     include_prerelease: bool,
+    asset_pattern: Option<&str>,
     preferred_arch: &str,
 ) -> Result<RepoMetadata, String> {
     let client = reqwest::Client::new();
     let url = format!("https://api.github.com/repos/{}/{}/releases", owner, repo);
 
-    let response = client
-        .get(&url)
-        .header(USER_AGENT, "axe-package-manager")
+    let response = api_request(&client, &url)
         .send()
         .await
         .map_err(|e| format!("Failed to send request: {}", e))?;
@@ -48,52 +108,667 @@ pub async fn find_github_asset(
         return Err(format!("No releases found for {}/{}", owner, repo));
     }
 
-    let arch_aliases = if preferred_arch == "x86_64" {
-        vec!["x86_64", "amd64", "x64", "64bit"]
-    } else if preferred_arch == "aarch64" {
-        vec!["aarch64", "arm64", "armv8", "armv8l"]
-    } else {
-        vec![preferred_arch]
-    };
-
     for release in releases {
         if !include_prerelease && release.prerelease {
             continue;
         }
+        if let Some(meta) = select_asset(release, asset_pattern, preferred_arch).await? {
+            return Ok(meta);
+        }
+    }
+
+    if let Some(pattern) = asset_pattern {
+        return Err(format!(
+            "No asset matching '{}' for architecture '{}' found in releases for {}/{}",
+            pattern, preferred_arch, owner, repo
+        ));
+    }
+
+    Err(format!(
+        "No valid AppImage for architecture '{}' found in releases for {}/{}",
+        preferred_arch, owner, repo
+    ))
+}
+
+/// Looks up the asset for the architecture in the release with the given tag
+pub async fn find_release_asset(
+    owner: &str,
+    repo: &str,
+    tag: &str,
+    asset_pattern: Option<&str>,
+    preferred_arch: &str,
+) -> Result<RepoMetadata, String> {
+    let client = reqwest::Client::new();
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/releases/tags/{}",
+        owner, repo, tag
+    );
+
+    let response = api_request(&client, &url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(format!("Release {} of {}/{} not found", tag, owner, repo));
+    }
+
+    let release: GithubRelease = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+    select_asset(release, asset_pattern, preferred_arch)
+        .await?
+        .ok_or_else(|| {
+            format!(
+                "No AppImage for architecture '{}' found in release {} of {}/{}",
+                preferred_arch, tag, owner, repo
+            )
+        })
+}
+
+/// Finds the artifact of the latest successful workflow run on `branch`
+///
+/// Runs of all workflows are considered unless `workflow` names one, e.g. `nightly.yml`.
+/// Artifacts named after AppImages are preferred, then ones named for the architecture; a run
+/// without a clear candidate is skipped for the one before it. The Actions API only serves
+/// artifacts to authenticated users, so without a token they are downloaded through
+/// nightly.link instead.
+pub async fn find_workflow_artifact(
+    owner: &str,
+    repo: &str,
+    branch: &str,
+    workflow: Option<&str>,
+    preferred_arch: &str,
+) -> Result<ArtifactMetadata, String> {
+    let client = reqwest::Client::new();
+    let runs_url = match workflow {
+        Some(workflow) => format!(
+            "https://api.github.com/repos/{}/{}/actions/workflows/{}/runs",
+            owner, repo, workflow
+        ),
+        None => format!(
+            "https://api.github.com/repos/{}/{}/actions/runs",
+            owner, repo
+        ),
+    };
+    let runs_url = reqwest::Url::parse_with_params(
+        &runs_url,
+        &[
+            ("branch", branch),
+            ("status", "success"),
+            ("per_page", "10"),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let response = api_request(&client, runs_url.as_str())
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(match workflow {
+            Some(workflow) => format!("Workflow {} not found in {}/{}", workflow, owner, repo),
+            None => format!("Repository {}/{} not found", owner, repo),
+        });
+    }
+    if !response.status().is_success() {
+        return Err(format!("GitHub API returned {}", response.status()));
+    }
+    let runs: WorkflowRuns = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+
+    let mut last_error = None;
+    for run in runs.workflow_runs {
+        let url = format!(
+            "https://api.github.com/repos/{}/{}/actions/runs/{}/artifacts",
+            owner, repo, run.id
+        );
+        let artifacts = match list_artifacts(&client, &url).await {
+            Ok(artifacts) => artifacts,
+            Err(e) => {
+                last_error = Some(e);
+                continue;
+            }
+        };
+
+        let available: Vec<&Artifact> = artifacts.iter().filter(|a| !a.expired).collect();
+        let Some(artifact) = select_artifact(&available, preferred_arch) else {
+            continue;
+        };
+
+        let url = match token() {
+            Some(_) => artifact.archive_download_url.clone(),
+            None => nightly_link(owner, repo, run.id, &artifact.name)?,
+        };
+        return Ok(ArtifactMetadata {
+            run_id: run.id,
+            name: artifact.name.clone(),
+            url,
+        });
+    }
 
-        let appimage_assets: Vec<&GithubAsset> = release
-            .assets
+    Err(match last_error {
+        Some(e) => format!("Failed to list workflow artifacts: {}", e),
+        None => format!(
+            "No successful workflow run with an AppImage artifact found on branch {} of {}/{}",
+            branch, owner, repo
+        ),
+    })
+}
+
+async fn list_artifacts(client: &reqwest::Client, url: &str) -> Result<Vec<Artifact>, String> {
+    let response = api_request(client, url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("GitHub API returned {}", response.status()));
+    }
+    let artifacts: Artifacts = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    Ok(artifacts.artifacts)
+}
+
+/// Picks the artifact most likely holding the AppImage for the architecture
+///
+/// Builds often upload logs or test results next to the AppImage, so a run with several
+/// artifacts and none named after AppImages or the architecture gives no answer.
+fn select_artifact<'a>(available: &[&'a Artifact], preferred_arch: &str) -> Option<&'a Artifact> {
+    let named = |artifact: &Artifact, part: &str| artifact.name.to_lowercase().contains(part);
+    let for_arch = |candidates: &[&'a Artifact]| {
+        host::arch_aliases(preferred_arch)
             .iter()
-            .filter(|a| a.name.to_lowercase().ends_with(".appimage"))
-            .collect();
+            .find_map(|arch| candidates.iter().copied().find(|a| named(a, arch)))
+    };
 
-        if appimage_assets.is_empty() {
+    let appimages: Vec<&Artifact> = available
+        .iter()
+        .copied()
+        .filter(|a| named(a, "appimage"))
+        .collect();
+    if !appimages.is_empty() {
+        return for_arch(&appimages).or(appimages.first().copied());
+    }
+    match available {
+        [only] => Some(only),
+        _ => for_arch(available),
+    }
+}
+
+/// nightly.link serves artifacts of public repositories without signing in
+fn nightly_link(owner: &str, repo: &str, run_id: u64, artifact: &str) -> Result<String, String> {
+    let mut url = reqwest::Url::parse("https://nightly.link/").map_err(|e| e.to_string())?;
+    url.path_segments_mut()
+        .map_err(|_| "Invalid nightly.link URL".to_string())?
+        .extend([
+            owner,
+            repo,
+            "actions",
+            "runs",
+            &run_id.to_string(),
+            &format!("{}.zip", artifact),
+        ]);
+    Ok(url.to_string())
+}
+
+/// Picks the AppImage for the architecture from a release
+///
+/// Archives are downloaded and looked into, so a release whose tarballs hold no AppImage
+/// (e.g. only a CLI) is passed over instead of failing the install later.
+async fn select_asset(
+    release: GithubRelease,
+    asset_pattern: Option<&str>,
+    preferred_arch: &str,
+) -> Result<Option<RepoMetadata>, String> {
+    let mut chosen = None;
+    for asset in asset_candidates(&release, asset_pattern, preferred_arch) {
+        if let Some(kind) = archive::kind(&asset.name)
+            && !archive_holds_appimage(asset, kind, preferred_arch).await?
+        {
             continue;
         }
+        chosen = Some(asset.clone());
+        break;
+    }
+
+    Ok(chosen.map(|asset| RepoMetadata {
+        checksum_asset: find_checksum_asset(&release.assets, &asset.name).cloned(),
+        asset,
+        version: release.tag_name,
+    }))
+}
+
+/// Assets of a release that may be the AppImage for the architecture, best first
+fn asset_candidates<'a>(
+    release: &'a GithubRelease,
+    asset_pattern: Option<&str>,
+    preferred_arch: &str,
+) -> Vec<&'a GithubAsset> {
+    let for_arch = |assets: &[&'a GithubAsset]| -> Vec<&'a GithubAsset> {
+        let found = host::arch_aliases(preferred_arch)
+            .iter()
+            .find_map(|arch| assets.iter().find(|a| a.name.to_lowercase().contains(arch)));
+        match found {
+            Some(asset) => vec![*asset],
+            None if preferred_arch == "x86_64" && assets.len() == 1 => assets.to_vec(),
+            None => Vec::new(),
+        }
+    };
 
-        for arch in &arch_aliases {
-            if let Some(asset) = appimage_assets
+    let matching: Vec<&GithubAsset> = release
+        .assets
+        .iter()
+        .filter(|a| match asset_pattern {
+            Some(pattern) => glob_match(pattern, &a.name),
+            None => a.name.to_lowercase().ends_with(".appimage"),
+        })
+        .collect();
+
+    // A pattern matching a single asset is taken as is
+    if asset_pattern.is_some() && matching.len() == 1 {
+        return matching;
+    }
+    if !matching.is_empty() || asset_pattern.is_some() {
+        return for_arch(&matching);
+    }
+
+    // Some projects only ship their AppImages inside archives, which may sit next to other
+    // tarballs, e.g. of a CLI, so every archive for the architecture is a candidate
+    let archives: Vec<&GithubAsset> = release
+        .assets
+        .iter()
+        .filter(|a| archive::kind(&a.name).is_some())
+        .collect();
+    let mut candidates: Vec<&GithubAsset> = archives
+        .iter()
+        .copied()
+        .filter(|a| {
+            host::arch_aliases(preferred_arch)
                 .iter()
-                .find(|a| a.name.to_lowercase().contains(arch))
-            {
-                return Ok(RepoMetadata {
-                    asset: (*asset).clone(),
-                    version: release.tag_name,
-                });
-            }
+                .any(|arch| a.name.to_lowercase().contains(arch))
+        })
+        .collect();
+    if candidates.is_empty() {
+        candidates = for_arch(&archives);
+    }
+    candidates.sort_by_key(|a| !a.name.to_lowercase().contains("appimage"));
+    candidates
+}
+
+/// Downloads an archive asset to check that it holds an AppImage for the architecture
+async fn archive_holds_appimage(
+    asset: &GithubAsset,
+    kind: archive::Kind,
+    arch: &str,
+) -> Result<bool, String> {
+    let dir = tempfile::Builder::new()
+        .prefix("axe-archive-")
+        .tempdir()
+        .map_err(|e| e.to_string())?;
+    let path = dir.path().join("archive");
+    download::fetch(&asset.browser_download_url, &path, &asset.name).await?;
+    Ok(matches!(
+        archive::find_appimage(&path, kind, arch),
+        Ok(Some(_))
+    ))
+}
+
+/// Names of checksum files listing several release assets, compared in lowercase
+const CHECKSUM_LISTS: &[&str] = &[
+    "sha256sums",
+    "sha256sums.txt",
+    "checksums.txt",
+    "checksums.sha256",
+];
+
+/// Finds a checksum file that may cover `asset_name`: `<asset>.sha256`, `SHA256SUMS`,
+/// `checksums.txt` or `<asset without extension>.sha256`
+fn find_checksum_asset<'a>(assets: &'a [GithubAsset], asset_name: &str) -> Option<&'a GithubAsset> {
+    let own = format!("{}.sha256", asset_name).to_lowercase();
+
+    assets
+        .iter()
+        .find(|a| a.name.to_lowercase() == own)
+        .or_else(|| {
+            assets
+                .iter()
+                .find(|a| CHECKSUM_LISTS.contains(&a.name.to_lowercase().as_str()))
+        })
+        .or_else(|| {
+            // `App-1.0.sha256` next to `App-1.0.AppImage`
+            let stem = asset_name
+                .rsplit_once('.')
+                .map_or(asset_name, |(stem, _)| stem)
+                .to_lowercase();
+            assets
+                .iter()
+                .find(|a| a.name.to_lowercase() == format!("{}.sha256", stem))
+        })
+}
+
+/// SHA-256 the release publishes for the asset, from GitHub's digest or a checksum file
+///
+/// A checksum list that only covers other assets is not an error: the download is then left
+/// unchecked, with a warning.
+pub async fn expected_hash(meta: &RepoMetadata) -> Result<Option<String>, String> {
+    if let Some(hash) = meta
+        .asset
+        .digest
+        .as_deref()
+        .and_then(|d| d.strip_prefix("sha256:"))
+    {
+        return Ok(Some(hash.to_lowercase()));
+    }
+
+    let Some(checksums) = &meta.checksum_asset else {
+        return Ok(None);
+    };
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(&checksums.browser_download_url)
+        .header(USER_AGENT, "axe-package-manager")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", checksums.name, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Failed to download {}: server returned {}",
+            checksums.name,
+            response.status()
+        ));
+    }
+    let content = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", checksums.name, e))?;
+
+    let list = CHECKSUM_LISTS.contains(&checksums.name.to_lowercase().as_str());
+    let hash = parse_checksums(&content, &meta.asset.name, !list);
+    if hash.is_none() {
+        eprintln!(
+            "Warning: {} has no checksum for {}, it can't be verified.",
+            checksums.name, meta.asset.name
+        );
+    }
+    Ok(hash)
+}
+
+/// Finds the hash for `file_name` in `sha256sum` output, or takes a lone hash as is when
+/// `lone_hash` is set, i.e. the file is named after the asset
+fn parse_checksums(content: &str, file_name: &str, lone_hash: bool) -> Option<String> {
+    let is_hash = |s: &str| s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit());
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    for line in &lines {
+        let mut parts = line.split_whitespace();
+        if let (Some(hash), Some(name)) = (parts.next(), parts.next())
+            && is_hash(hash)
+            && name.trim_start_matches('*').rsplit('/').next() == Some(file_name)
+        {
+            return Some(hash.to_lowercase());
         }
+    }
 
-        if preferred_arch == "x86_64" && appimage_assets.len() == 1 {
-            let asset = appimage_assets[0];
-            return Ok(RepoMetadata {
-                asset: (*asset).clone(),
-                version: release.tag_name,
-            });
+    match lines.as_slice() {
+        [line] if lone_hash && line.split_whitespace().count() == 1 && is_hash(line.trim()) => {
+            Some(line.trim().to_lowercase())
         }
+        _ => None,
     }
+}
 
-    Err(format!(
-        "No valid AppImage for architecture '{}' found in releases for {}/{}",
-        preferred_arch, owner, repo
-    ))
+/// Matches a file name against a glob with `*` and `?` wildcards
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Searches GitHub for repositories matching the term that mention AppImage
+pub async fn search_repositories(term: &str) -> Result<Vec<SearchRepo>, String> {
+    let client = reqwest::Client::new();
+    let url = reqwest::Url::parse_with_params(
+        "https://api.github.com/search/repositories",
+        &[
+            ("q", format!("{} appimage", term)),
+            ("per_page", "10".into()),
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let response = api_request(&client, url.as_str())
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("GitHub search failed: {}", response.status()));
+    }
+
+    let results: SearchResponse = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    Ok(results.items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets(names: &[&str]) -> Vec<GithubAsset> {
+        names
+            .iter()
+            .map(|name| GithubAsset {
+                name: name.to_string(),
+                browser_download_url: format!("https://example.com/{}", name),
+                digest: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_checksum_files_for_the_asset() {
+        let release = assets(&["App.AppImage", "App.AppImage.sha256", "SHA256SUMS"]);
+        let found = find_checksum_asset(&release, "App.AppImage").unwrap();
+        assert_eq!(found.name, "App.AppImage.sha256");
+
+        let release = assets(&["App.AppImage", "SHA256SUMS"]);
+        let found = find_checksum_asset(&release, "App.AppImage").unwrap();
+        assert_eq!(found.name, "SHA256SUMS");
+
+        let release = assets(&["App-1.0.AppImage", "App-1.0.sha256"]);
+        let found = find_checksum_asset(&release, "App-1.0.AppImage").unwrap();
+        assert_eq!(found.name, "App-1.0.sha256");
+    }
+
+    fn candidates(names: &[&str], pattern: Option<&str>, arch: &str) -> Vec<String> {
+        let release = GithubRelease {
+            tag_name: "v1.0".into(),
+            assets: assets(names),
+            prerelease: false,
+        };
+        asset_candidates(&release, pattern, arch)
+            .into_iter()
+            .map(|a| a.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn prefers_appimages_for_the_architecture() {
+        let names = [
+            "App-x86_64.AppImage",
+            "App-aarch64.AppImage",
+            "App-x86_64.tar.gz",
+        ];
+        assert_eq!(
+            candidates(&names, None, "aarch64"),
+            ["App-aarch64.AppImage"]
+        );
+        assert_eq!(candidates(&names, None, "x86_64"), ["App-x86_64.AppImage"]);
+        assert!(candidates(&names, None, "riscv64").is_empty());
+
+        // A lone AppImage without an architecture in its name is taken for x86_64 only
+        assert_eq!(
+            candidates(&["App.AppImage"], None, "x86_64"),
+            ["App.AppImage"]
+        );
+        assert!(candidates(&["App.AppImage"], None, "aarch64").is_empty());
+    }
+
+    #[test]
+    fn patterns_select_assets() {
+        let names = ["app-1.0-amd64.bin", "app-1.0-arm64.bin"];
+        assert_eq!(
+            candidates(&names, Some("app-*-arm64.bin"), "x86_64"),
+            ["app-1.0-arm64.bin"]
+        );
+        assert_eq!(
+            candidates(&names, Some("app-*.bin"), "aarch64"),
+            ["app-1.0-arm64.bin"]
+        );
+        assert!(candidates(&["App-x86_64.tar.gz"], Some("*.AppImage"), "x86_64").is_empty());
+    }
+
+    #[test]
+    fn falls_back_to_every_archive_for_the_architecture() {
+        let names = [
+            "app-cli-x86_64.tar.gz",
+            "app-appimage-x86_64.zip",
+            "app-aarch64.tar.gz",
+            "SHA256SUMS",
+        ];
+        assert_eq!(
+            candidates(&names, None, "x86_64"),
+            ["app-appimage-x86_64.zip", "app-cli-x86_64.tar.gz"]
+        );
+        assert_eq!(candidates(&names, None, "aarch64"), ["app-aarch64.tar.gz"]);
+        assert_eq!(candidates(&["app.tar.gz"], None, "x86_64"), ["app.tar.gz"]);
+        assert!(candidates(&["a.tar.gz", "b.zip"], None, "x86_64").is_empty());
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_match(
+            "App-*-x86_64.AppImage",
+            "App-1.2.3-x86_64.AppImage"
+        ));
+        assert!(glob_match("App-*", "App-"));
+        assert!(glob_match("*.AppImage", "App.AppImage"));
+        assert!(glob_match("App-?.AppImage", "App-1.AppImage"));
+        assert!(glob_match("*-*-x86_64*", "App-1-x86_64.AppImage"));
+        assert!(!glob_match("App-?.AppImage", "App-10.AppImage"));
+        assert!(!glob_match(
+            "App-*-x86_64.AppImage",
+            "App-1.2.3-aarch64.AppImage"
+        ));
+        assert!(!glob_match("*.AppImage", "App.AppImage.zsync"));
+    }
+
+    #[test]
+    fn parses_checksum_files() {
+        let hash = "a".repeat(64);
+        let other = "b".repeat(64);
+
+        let sums = format!("{}  Other.AppImage\n{}  App.AppImage\n", other, hash);
+        assert_eq!(
+            parse_checksums(&sums, "App.AppImage", true),
+            Some(hash.clone())
+        );
+
+        // Binary mode marker and directories in the name
+        let sums = format!("{} *dist/App.AppImage\n", hash.to_uppercase());
+        assert_eq!(
+            parse_checksums(&sums, "App.AppImage", true),
+            Some(hash.clone())
+        );
+
+        // A file holding nothing but the hash, only trusted when named after the asset
+        let lone = format!("{}\n", hash);
+        assert_eq!(parse_checksums(&lone, "App.AppImage", true), Some(hash));
+        assert_eq!(parse_checksums(&lone, "App.AppImage", false), None);
+
+        let sums = format!("{}  Other.AppImage\n", other);
+        assert_eq!(parse_checksums(&sums, "App.AppImage", true), None);
+        assert_eq!(parse_checksums("not a hash\n", "App.AppImage", true), None);
+    }
+
+    fn artifacts(names: &[&str]) -> Vec<Artifact> {
+        names
+            .iter()
+            .map(|name| Artifact {
+                name: name.to_string(),
+                archive_download_url: String::new(),
+                expired: false,
+            })
+            .collect()
+    }
+
+    fn select<'a>(available: &'a [Artifact], arch: &str) -> Option<&'a str> {
+        let available: Vec<&Artifact> = available.iter().collect();
+        select_artifact(&available, arch).map(|a| a.name.as_str())
+    }
+
+    #[test]
+    fn prefers_appimage_artifacts() {
+        let run = artifacts(&[
+            "test-results",
+            "tool-appimage-aarch64",
+            "tool-appimage-x86_64",
+        ]);
+        assert_eq!(select(&run, "x86_64"), Some("tool-appimage-x86_64"));
+
+        let run = artifacts(&["logs-x86_64", "AppImage"]);
+        assert_eq!(select(&run, "x86_64"), Some("AppImage"));
+
+        let run = artifacts(&["tool-linux-x86_64", "test-results"]);
+        assert_eq!(select(&run, "x86_64"), Some("tool-linux-x86_64"));
+
+        let run = artifacts(&["build"]);
+        assert_eq!(select(&run, "x86_64"), Some("build"));
+    }
+
+    #[test]
+    fn skips_runs_without_a_clear_artifact() {
+        let run = artifacts(&["coverage", "test-results"]);
+        assert_eq!(select(&run, "x86_64"), None);
+    }
+
+    #[test]
+    fn ignores_checksum_files_of_other_assets() {
+        let release = assets(&["App.AppImage", "App.deb", "App-installer.sha256"]);
+        assert!(find_checksum_asset(&release, "App.AppImage").is_none());
+    }
 }
@@ -0,0 +1,135 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+use target_lexicon::{Architecture, Triple};
+
+/// Architecture names used by axe, with the spellings found in release asset names
+const ARCHITECTURES: &[(&str, &[&str])] = &[
+    ("x86_64", &["x86_64", "amd64", "x64", "64bit"]),
+    ("aarch64", &["aarch64", "arm64", "armv8", "armv8l"]),
+    ("armhf", &["armhf", "armv7l", "armv7", "armv7hf", "arm32"]),
+    ("i686", &["i686", "i386", "i586", "ia32", "32bit"]),
+    ("riscv64", &["riscv64"]),
+    ("ppc64le", &["ppc64le", "ppc64el", "powerpc64le"]),
+    ("s390x", &["s390x"]),
+    ("loongarch64", &["loongarch64", "loong64"]),
+];
+
+/// Returns true when the process runs with an effective uid of 0
+pub fn is_root() -> bool {
+    let Ok(status) = fs::read_to_string("/proc/self/status") else {
+        return false;
+    };
+
+    // Uid: <real> <effective> <saved> <fs>
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|uids| uids.split_whitespace().nth(1))
+        .is_some_and(|euid| euid == "0")
+}
+
+/// Returns the mount options of the filesystem containing `path`
+pub fn mount_options(path: &Path) -> Option<Vec<String>> {
+    let path = path.canonicalize().ok()?;
+    let mounts = fs::read_to_string("/proc/self/mounts").ok()?;
+
+    // The longest mount point that prefixes the path is the one it lives on
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let mount_point = fields.get(1)?.replace("\\040", " ");
+            let options = fields.get(3)?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point, options.split(',').map(String::from).collect()))
+        })
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, options)| options)
+}
+
+pub fn is_noexec(path: &Path) -> bool {
+    mount_options(path).is_some_and(|opts| opts.iter().any(|o| o == "noexec"))
+}
+
+/// Looks for the FUSE 2 library that type 2 AppImage runtimes load at startup
+pub fn has_libfuse2() -> bool {
+    if let Ok(output) = Command::new("ldconfig").arg("-p").output()
+        && String::from_utf8_lossy(&output.stdout).contains("libfuse.so.2")
+    {
+        return true;
+    }
+
+    [
+        "/lib",
+        "/lib64",
+        "/usr/lib",
+        "/usr/lib64",
+        "/lib/x86_64-linux-gnu",
+        "/usr/lib/x86_64-linux-gnu",
+        "/lib/aarch64-linux-gnu",
+        "/usr/lib/aarch64-linux-gnu",
+    ]
+    .iter()
+    .any(|dir| Path::new(dir).join("libfuse.so.2").exists())
+}
+
+/// Whether AppImages can be mounted with FUSE instead of being extracted
+pub fn fuse_available() -> bool {
+    Path::new("/dev/fuse").exists() && has_libfuse2()
+}
+
+/// Returns false when unprivileged user namespaces are disabled by a sysctl
+pub fn user_namespaces_enabled() -> bool {
+    let read = |p: &str| fs::read_to_string(p).ok().map(|v| v.trim().to_string());
+
+    if read("/proc/sys/kernel/unprivileged_userns_clone").is_some_and(|v| v == "0") {
+        return false;
+    }
+    if read("/proc/sys/user/max_user_namespaces").is_some_and(|v| v == "0") {
+        return false;
+    }
+    read("/proc/sys/kernel/apparmor_restrict_unprivileged_userns").is_none_or(|v| v != "1")
+}
+
+/// Finds an executable in PATH
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|p| p.is_file())
+}
+
+/// Architecture of the machine axe was built for
+pub fn arch() -> &'static str {
+    match Triple::host().architecture {
+        Architecture::X86_64 | Architecture::X86_64h => "x86_64",
+        Architecture::Aarch64(_) => "aarch64",
+        Architecture::Arm(_) => "armhf",
+        Architecture::X86_32(_) => "i686",
+        Architecture::Riscv64(_) => "riscv64",
+        Architecture::Powerpc64le => "ppc64le",
+        Architecture::S390x => "s390x",
+        Architecture::LoongArch64 => "loongarch64",
+        _ => "x86_64",
+    }
+}
+
+/// Maps any known spelling of an architecture (e.g. `amd64`) to the name axe uses
+pub fn normalize_arch(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    ARCHITECTURES
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&name.as_str()))
+        .map(|(arch, _)| *arch)
+}
+
+/// Spellings of an architecture to look for in asset names, most specific first
+pub fn arch_aliases(arch: &str) -> Vec<&str> {
+    ARCHITECTURES
+        .iter()
+        .find(|(name, _)| *name == arch)
+        .map(|(_, aliases)| aliases.to_vec())
+        .unwrap_or_else(|| vec![arch])
+}
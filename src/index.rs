@@ -0,0 +1,251 @@
+use crate::{
+    cli::Source,
+    config::{self, AxePaths},
+};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+/// A package index hosted by a team, listing packages installable by bare name
+///
+/// ```toml
+/// [packages.our-internal-tool]
+/// source = "acme/internal-tool"
+/// asset_pattern = "internal-tool-*-x86_64.AppImage"
+/// description = "Our internal tool"
+/// desktop = true
+/// ```
+#[derive(Deserialize)]
+pub struct Index {
+    #[serde(default)]
+    pub packages: BTreeMap<String, IndexEntry>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct IndexEntry {
+    /// GitHub repo 'owner/repo', a URL or 'catalog:<AppName>'
+    pub source: String,
+    pub asset_pattern: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub desktop: bool,
+    #[serde(default)]
+    pub portable: bool,
+}
+
+/// Parses an index as JSON or TOML, depending on its content
+pub fn parse(content: &str) -> Result<Index, String> {
+    if content.trim_start().starts_with('{') {
+        serde_json::from_str(content).map_err(|e| format!("Invalid JSON index: {}", e))
+    } else {
+        toml::from_str(content).map_err(|e| format!("Invalid TOML index: {}", e))
+    }
+}
+
+/// Downloads or reads the index, validates it and stores it in the local cache
+pub async fn fetch(paths: &AxePaths, name: &str, location: &str) -> Result<Index, String> {
+    config::validate_name(name)?;
+    let content = if is_remote(location) {
+        let response = reqwest::get(location)
+            .await
+            .map_err(|e| format!("Failed to download index '{}': {}", name, e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Failed to download index '{}': server returned {}",
+                name,
+                response.status()
+            ));
+        }
+        response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to download index '{}': {}", name, e))?
+    } else {
+        let path = config::file_path(location).unwrap_or_else(|| PathBuf::from(location));
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read index '{}': {}", name, e))?
+    };
+
+    let index = parse(&content)?;
+
+    let cache = paths.index_cache_path(name);
+    if let Some(parent) = cache.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&cache, content).map_err(|e| format!("Failed to cache index: {}", e))?;
+    Ok(index)
+}
+
+fn is_remote(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// Loads an index from the local cache, fetching it when there is no cached copy
+pub async fn load(paths: &AxePaths, name: &str, location: &str) -> Result<Index, String> {
+    config::validate_name(name)?;
+    match fs::read_to_string(paths.index_cache_path(name)) {
+        Ok(content) => parse(&content),
+        Err(_) => fetch(paths, name, location).await,
+    }
+}
+
+/// Finds a package by name in the configured indexes, in alphabetical order of the indexes
+///
+/// Only indexes on this machine may point to local files: a downloaded index could otherwise
+/// make axe copy any file it can read.
+pub async fn resolve(paths: &AxePaths, package: &str) -> Result<(String, IndexEntry), String> {
+    let config = paths.load_config()?;
+    if config.indexes.is_empty() {
+        return Err(format!(
+            "'{}' is not a valid source and no package indexes are configured. \
+             Use 'owner/repo', a URL, 'catalog:<AppName>' or add an index with 'axe repo add'.",
+            package
+        ));
+    }
+
+    for (name, location) in &config.indexes {
+        match load(paths, name, location).await {
+            Ok(index) => {
+                if let Some(entry) = index.packages.get(package) {
+                    if is_remote(location)
+                        && matches!(entry.source.parse::<Source>(), Ok(Source::Local(_)))
+                    {
+                        return Err(format!(
+                            "Package index '{}' is downloaded, its entry for '{}' can't point to \
+                             the local file '{}'",
+                            name, package, entry.source
+                        ));
+                    }
+                    return Ok((name.clone(), entry.clone()));
+                }
+            }
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+
+    Err(format!(
+        "'{}' was not found in any package index. Run 'axe repo update' to refresh them.",
+        package
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::path::Path;
+
+    const TOML: &str = r#"
+[packages.tool]
+source = "acme/tool"
+asset_pattern = "tool-*-x86_64.AppImage"
+desktop = true
+
+[packages.local]
+source = "/srv/apps/Local.AppImage"
+"#;
+
+    #[test]
+    fn parses_toml_and_json() {
+        let index = parse(TOML).unwrap();
+        let tool = &index.packages["tool"];
+        assert_eq!(tool.source, "acme/tool");
+        assert_eq!(
+            tool.asset_pattern.as_deref(),
+            Some("tool-*-x86_64.AppImage")
+        );
+        assert!(tool.desktop);
+        assert!(!tool.prerelease && !tool.portable);
+
+        let json = r#"{"packages": {"tool": {"source": "catalog:Tool", "prerelease": true}}}"#;
+        let index = parse(json).unwrap();
+        assert_eq!(index.packages["tool"].source, "catalog:Tool");
+        assert!(index.packages["tool"].prerelease);
+
+        assert!(parse("").unwrap().packages.is_empty());
+        assert!(parse("{ not json").is_err());
+        assert!(parse("[packages.tool]\ndesktop = true\n").is_err());
+    }
+
+    fn paths(dir: &Path) -> AxePaths {
+        AxePaths {
+            config_dir: dir.join("config"),
+            data_dir: dir.join("data"),
+            bin_dir: dir.join("data/bin"),
+            applications_dir: dir.join("applications"),
+            link_dir: None,
+            system: false,
+        }
+    }
+
+    /// Configures the indexes, caching each one's content so nothing is downloaded
+    fn configure(paths: &AxePaths, indexes: &[(&str, &str, &str)]) {
+        fs::create_dir_all(&paths.config_dir).unwrap();
+        let config = Config {
+            indexes: indexes
+                .iter()
+                .map(|(name, location, _)| (name.to_string(), location.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+        paths.save_config(&config).unwrap();
+        for (name, _, content) in indexes {
+            let cache = paths.index_cache_path(name);
+            fs::create_dir_all(cache.parent().unwrap()).unwrap();
+            fs::write(cache, content).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_packages_in_index_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+        let other = "[packages.tool]\nsource = \"other/tool\"\n";
+        configure(
+            &paths,
+            &[
+                ("b-team", "https://example.com/index.toml", TOML),
+                ("a-team", "https://example.com/other.toml", other),
+            ],
+        );
+
+        let (index, entry) = resolve(&paths, "tool").await.unwrap();
+        assert_eq!(
+            (index.as_str(), entry.source.as_str()),
+            ("a-team", "other/tool")
+        );
+        assert!(resolve(&paths, "missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn only_local_indexes_point_to_local_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+        let file_url = "[packages.local]\nsource = \"file:///srv/apps/Local.AppImage\"\n";
+        configure(
+            &paths,
+            &[("remote", "https://example.com/index.toml", TOML)],
+        );
+        assert!(resolve(&paths, "local").await.is_err());
+        assert!(resolve(&paths, "tool").await.is_ok());
+
+        configure(
+            &paths,
+            &[("remote", "https://example.com/index.toml", file_url)],
+        );
+        assert!(resolve(&paths, "local").await.is_err());
+
+        let location = dir.path().join("index.toml");
+        fs::write(&location, TOML).unwrap();
+        configure(&paths, &[("shared", &location.to_string_lossy(), TOML)]);
+        let (_, entry) = resolve(&paths, "local").await.unwrap();
+        assert_eq!(entry.source, "/srv/apps/Local.AppImage");
+    }
+
+    #[tokio::test]
+    async fn requires_configured_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = paths(dir.path());
+        assert!(resolve(&paths, "tool").await.is_err());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::{
+    config::{AxePaths, PackageEntry, RunMode},
+    host, sandbox,
+};
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// Resolves `RunMode::Auto` against the capabilities of this machine
+pub fn effective_mode(pkg: &PackageEntry) -> RunMode {
+    match pkg.run_mode {
+        RunMode::Auto if host::fuse_available() => RunMode::Fuse,
+        RunMode::Auto => RunMode::ExtractAndRun,
+        mode => mode,
+    }
+}
+
+/// Builds the command that starts the package according to its run mode and sandbox
+pub fn command(pkg: &PackageEntry, paths: &AxePaths) -> Result<Command, String> {
+    let mut mode = effective_mode(pkg);
+
+    // FUSE can't mount inside the sandbox's user namespace
+    if pkg.sandbox.is_some() && mode == RunMode::Fuse {
+        mode = RunMode::ExtractAndRun;
+    }
+
+    let mut inner = match mode {
+        RunMode::ExtractAndRun => {
+            let mut cmd = Command::new(&pkg.path);
+            cmd.env("APPIMAGE_EXTRACT_AND_RUN", "1");
+            cmd
+        }
+        RunMode::Extracted => {
+            let app_dir = paths.extracted_dir(&pkg.name).join("squashfs-root");
+            if !app_dir.join("AppRun").exists() {
+                println!("Extracting {}...", pkg.name);
+                extract(pkg, paths)?;
+            }
+
+            // Mimic the environment the AppImage runtime sets up
+            let mut cmd = Command::new(app_dir.join("AppRun"));
+            cmd.env("APPIMAGE", &pkg.path)
+                .env("APPDIR", &app_dir)
+                .env("ARGV0", &pkg.name);
+
+            // The runtime would switch to the portable dirs itself
+            let [home, config] = pkg.portable_dirs();
+            if home.is_dir() {
+                cmd.env("HOME", home);
+            }
+            if config.is_dir() {
+                cmd.env("XDG_CONFIG_HOME", config);
+            }
+            cmd
+        }
+        _ => Command::new(&pkg.path),
+    };
+
+    inner.envs(&pkg.env).args(&pkg.args);
+    if let Some(dir) = &pkg.working_dir {
+        inner.current_dir(dir);
+    }
+
+    let Some(profile) = &pkg.sandbox else {
+        return Ok(inner);
+    };
+
+    let mut exposed = vec![pkg.path.clone()];
+    if let Ok(real_path) = pkg.path.canonicalize()
+        && real_path != pkg.path
+    {
+        exposed.push(real_path);
+    }
+    if mode == RunMode::Extracted {
+        exposed.push(paths.extracted_dir(&pkg.name));
+    }
+    // Portable data must stay writable inside the sandbox
+    let mut profile = profile.clone();
+    if pkg.portable {
+        profile.paths.extend(pkg.portable_dirs());
+    }
+
+    sandbox::wrap(&inner, &profile, &exposed)
+}
+
+/// Builds the `Exec=` value of the package's desktop entry
+///
+/// Packages that need more than a plain exec go through `axe run`, so launching from
+/// the menu behaves exactly like launching from the terminal.
+pub fn desktop_exec(pkg: &PackageEntry, paths: &AxePaths) -> String {
+    if pkg.sandbox.is_none() && effective_mode(pkg) == RunMode::Fuse {
+        let mut parts = Vec::new();
+        if !pkg.env.is_empty() {
+            parts.push("env".to_string());
+            parts.extend(
+                pkg.env
+                    .iter()
+                    .map(|(key, value)| quote_exec_arg(&format!("{}={}", key, value))),
+            );
+        }
+        parts.push(quote_exec_arg(&pkg.path.to_string_lossy()));
+        parts.extend(pkg.args.iter().map(|a| quote_exec_arg(a)));
+        return parts.join(" ");
+    }
+
+    // System-wide entries are shared by every account, which may not be able to run this
+    // user's copy of axe (e.g. ~/.cargo/bin/axe), so they look it up in PATH
+    let axe = if paths.system {
+        PathBuf::from("axe")
+    } else {
+        env::current_exe().unwrap_or_else(|_| PathBuf::from("axe"))
+    };
+    let mut parts = vec![quote_exec_arg(&axe.to_string_lossy())];
+    if paths.system {
+        parts.push("--system".to_string());
+    }
+    parts.push("run".to_string());
+    parts.push(quote_exec_arg(&pkg.name));
+    parts.join(" ")
+}
+
+/// Quotes an argument following the desktop entry spec's rules for `Exec=`
+fn quote_exec_arg(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    // An empty argument has to be quoted, or it would disappear
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:+,@%".contains(c))
+    {
+        return arg;
+    }
+
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+
+    // Backslashes are escaped a second time because Exec is itself a string value, which
+    // also can't hold line breaks
+    quoted
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t")
+}
+
+/// Replaces the persistent extracted copy of a package with its current binary
+pub fn extract(pkg: &PackageEntry, paths: &AxePaths) -> Result<PathBuf, String> {
+    let dir = paths.extracted_dir(&pkg.name);
+    remove_extracted(&pkg.name, paths)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+
+    let status = Command::new(&pkg.path)
+        .arg("--appimage-extract")
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .status()
+        .map_err(|e| format!("Failed to extract {}: {}", pkg.name, e))?;
+
+    if !status.success() || !dir.join("squashfs-root/AppRun").exists() {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(format!(
+            "Failed to extract {} (only type 2 AppImages can be extracted)",
+            pkg.name
+        ));
+    }
+    Ok(dir)
+}
+
+pub fn remove_extracted(name: &str, paths: &AxePaths) -> Result<(), String> {
+    let dir = paths.extracted_dir(name);
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove {:?}: {}", dir, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_exec_arguments() {
+        assert_eq!(quote_exec_arg("/opt/axe/bin/app"), "/opt/axe/bin/app");
+        assert_eq!(quote_exec_arg("KEY=a,b:c"), "KEY=a,b:c");
+        assert_eq!(quote_exec_arg("My App"), r#""My App""#);
+        assert_eq!(quote_exec_arg("100%"), "100%%");
+        assert_eq!(quote_exec_arg("%f x"), r#""%%f x""#);
+        assert_eq!(quote_exec_arg("it's"), r#""it's""#);
+        assert_eq!(
+            quote_exec_arg(r#"say "hi" $HOME `id`"#),
+            r#""say \\"hi\\" \\$HOME \\`id\\`""#
+        );
+        assert_eq!(quote_exec_arg(r"C:\dir"), r#""C:\\\\dir""#);
+        assert_eq!(quote_exec_arg("a\nExec=evil"), r#""a\nExec=evil""#);
+        assert_eq!(quote_exec_arg(""), r#""""#);
+    }
+
+    fn package(run_mode: RunMode) -> PackageEntry {
+        let mut pkg = PackageEntry::new(
+            "My App".into(),
+            "1.0".into(),
+            "https://example.com/app.AppImage".into(),
+            String::new(),
+            "/home/me/.local/share/axe/bin/My App.AppImage".into(),
+            crate::config::Source::Local { modified: None },
+        );
+        pkg.run_mode = run_mode;
+        pkg
+    }
+
+    #[test]
+    fn execs_plain_packages_directly() {
+        let mut pkg = package(RunMode::Fuse);
+        pkg.env.insert("LANG".into(), "C".into());
+        pkg.env.insert("GREETING".into(), "hello world".into());
+        pkg.args = vec!["--open".into(), "%f".into()];
+        assert_eq!(
+            desktop_exec(&pkg, &AxePaths::system()),
+            r#"env "GREETING=hello world" LANG=C "/home/me/.local/share/axe/bin/My App.AppImage" --open %%f"#
+        );
+    }
+
+    #[test]
+    fn runs_other_packages_through_axe() {
+        let pkg = package(RunMode::Extracted);
+        assert_eq!(
+            desktop_exec(&pkg, &AxePaths::system()),
+            r#"axe --system run "My App""#
+        );
+
+        let exe = env::current_exe().unwrap();
+        assert_eq!(
+            desktop_exec(&pkg, &AxePaths::new().unwrap()),
+            format!(r#"{} run "My App""#, quote_exec_arg(&exe.to_string_lossy()))
+        );
+    }
+}
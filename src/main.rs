@@ -1,28 +1,90 @@
 use crate::cli::{Commands, parse_args};
 use crate::config::AxePaths;
 
+mod appimage;
+mod archive;
+mod cache;
+mod catalog;
 mod cli;
 mod commands;
 mod config;
+mod doctor;
 mod download;
 mod github;
+mod host;
+mod index;
+mod launch;
+mod output;
+mod page;
+mod sandbox;
+mod signature;
+mod squashfs;
 
 #[tokio::main]
 async fn main() {
-    let paths = AxePaths::new().expect("Failed to initialize paths");
-    paths
-        .ensure_dirs()
-        .expect("Failed to create necessary directories");
-
     let cli = parse_args();
 
+    let user_paths = AxePaths::new().expect("Failed to initialize paths");
+    let user_config = user_paths.load_config().unwrap_or_else(|e| {
+        eprintln!("Warning: Failed to parse config: {}", e);
+        Default::default()
+    });
+    // sudo switches HOME to root's, so the machine-wide default lives in /etc/axe
+    let system_config = AxePaths::system().load_config().unwrap_or_else(|e| {
+        eprintln!("Warning: Failed to parse the system config: {}", e);
+        Default::default()
+    });
+
+    let paths = if cli.system || system_config.system || user_config.system {
+        AxePaths::system()
+    } else {
+        user_paths
+    };
+
+    if paths.system && !host::is_root() {
+        if cli.command.modifies() {
+            eprintln!(
+                "System mode manages files in {:?} and {:?}, which requires root privileges.",
+                paths.data_dir, paths.applications_dir
+            );
+            eprintln!("Please re-run the command with sudo.");
+            std::process::exit(1);
+        }
+    } else {
+        paths
+            .ensure_dirs()
+            .expect("Failed to create necessary directories");
+    }
+
+    // update and outdated keep each package's own architecture unless --arch is given
+    let arch = cli.arch.clone().unwrap_or_else(|| host::arch().to_string());
+
     match cli.command {
-        Commands::Add(a) => commands::handle_add(a, &paths).await,
-        Commands::List => commands::handle_list(&paths),
-        Commands::Install => commands::handle_install(&paths).await,
-        Commands::Run(a) => commands::handle_run(a, &paths).await,
+        Commands::Add(a) => commands::handle_add(a, &paths, &arch, cli.offline).await,
+        Commands::List => commands::handle_list(&paths, cli.output),
+        Commands::Install => commands::handle_install(&paths, &arch, cli.offline).await,
+        Commands::Run(a) => commands::handle_run(a, &paths, cli.offline).await,
         Commands::Rename(a) => commands::handle_rename(a, &paths),
-        Commands::Update(a) => commands::handle_update(a, &paths).await,
+        Commands::Update(a) => {
+            commands::handle_update(a, &paths, cli.arch.as_deref(), cli.offline).await
+        }
         Commands::Remove(a) => commands::handle_remove(a, &paths),
+        Commands::Adopt(a) => commands::handle_adopt(a, &paths).await,
+        Commands::Clean(a) => commands::handle_clean(a, &paths),
+        Commands::Doctor => commands::handle_doctor(&paths).await,
+        Commands::SetRunMode(a) => commands::handle_set_run_mode(a, &paths),
+        Commands::Sandbox(a) => commands::handle_sandbox(a, &paths),
+        Commands::SetEnv(a) => commands::handle_set_env(a, &paths),
+        Commands::SetArgs(a) => commands::handle_set_args(a, &paths),
+        Commands::SetPortable(a) => commands::handle_set_portable(a, &paths),
+        Commands::Data(a) => commands::handle_data(a, &paths),
+        Commands::Verify => commands::handle_verify(&paths, cli.output),
+        Commands::Outdated => {
+            commands::handle_outdated(&paths, cli.output, cli.arch.as_deref(), cli.offline).await
+        }
+        Commands::Info(a) => commands::handle_info(a, &paths, cli.output),
+        Commands::Search(a) => commands::handle_search(a, &paths, &arch, cli.offline).await,
+        Commands::Repo(a) => commands::handle_repo(a, &paths).await,
+        Commands::Cache(a) => commands::handle_cache(a, &paths),
     }
 }
@@ -0,0 +1,109 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable tables and messages
+    Text,
+    Json,
+    Toml,
+}
+
+// The records below are printed for scripts, so their field names are part of the
+// stable interface. Add fields, but don't rename or remove them.
+
+#[derive(Serialize)]
+pub struct Records<T> {
+    pub packages: Vec<T>,
+}
+
+#[derive(Serialize)]
+pub struct ListRecord {
+    pub name: String,
+    pub version: String,
+    /// "github", "direct", "artifact", "page" or "local"
+    pub source: String,
+    /// Where updates come from, by source: `owner/repo` for "github", `owner/repo@branch`
+    /// for "artifact", the download URL for "direct", the scraped page for "page" and the
+    /// file path for "local"
+    pub origin: String,
+    pub path: PathBuf,
+    pub installed: bool,
+}
+
+#[derive(Serialize)]
+pub struct VerifyRecord {
+    pub name: String,
+    pub path: PathBuf,
+    /// "ok", "missing" or "modified"
+    pub status: String,
+    pub expected_hash: String,
+    pub actual_hash: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct OutdatedRecord {
+    pub name: String,
+    pub installed_version: String,
+    pub available_version: Option<String>,
+    /// Same as [`ListRecord::origin`]
+    pub origin: String,
+    /// "outdated", "up-to-date" or "error"; an outdated package without `available_version`
+    /// has changed, but the new version is unknown
+    pub status: String,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct InfoRecord {
+    pub name: String,
+    pub version: String,
+    /// Same as [`ListRecord::source`]
+    pub source: String,
+    /// Same as [`ListRecord::origin`]
+    pub origin: String,
+    pub url: String,
+    pub hash: String,
+    pub path: PathBuf,
+    pub installed: bool,
+    /// File size in bytes
+    pub size: Option<u64>,
+    /// Unix time the current version was installed
+    pub installed_at: Option<u64>,
+    pub desktop_file: Option<PathBuf>,
+    /// App name in the AppImageHub catalog, if added with `catalog:<AppName>`
+    pub catalog_id: Option<String>,
+    /// Fingerprint of the key that signed the AppImage
+    pub signer: Option<String>,
+    /// Metadata embedded in the AppImage, absent when it isn't installed
+    pub appimage: Option<AppImageRecord>,
+}
+
+#[derive(Serialize)]
+pub struct AppImageRecord {
+    /// From the embedded desktop file
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub appimage_type: Option<u8>,
+    pub architecture: Option<String>,
+    pub update_information: Option<String>,
+}
+
+/// Prints records in a structured format, does nothing for `OutputFormat::Text`
+pub fn print<T: Serialize>(format: OutputFormat, packages: Vec<T>) {
+    let records = Records { packages };
+    let result = match format {
+        OutputFormat::Text => return,
+        OutputFormat::Json => serde_json::to_string_pretty(&records).map_err(|e| e.to_string()),
+        OutputFormat::Toml => toml::to_string_pretty(&records).map_err(|e| e.to_string()),
+    };
+
+    match result {
+        Ok(text) => println!("{}", text.trim_end()),
+        Err(e) => {
+            eprintln!("Failed to serialize output: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use regex::Regex;
+use reqwest::Url;
+use std::{cmp::Ordering, sync::LazyLock};
+
+static HREF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)href\s*=\s*["']([^"']+)["']"#).unwrap());
+
+/// A download link found on a vendor page
+pub struct PageLink {
+    pub url: String,
+    /// Captured by the pattern's `version` group, or its first group
+    pub version: Option<String>,
+}
+
+/// Compiles a link pattern, with an error message fit for the command line
+pub fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))
+}
+
+/// Scrapes `page` for links matching `pattern` and returns the newest one
+///
+/// Links are matched as absolute URLs. When the pattern captures a version, the highest
+/// version wins; otherwise the first matching link on the page is taken.
+pub async fn find_latest(page: &str, pattern: &str) -> Result<PageLink, String> {
+    let pattern = compile(pattern)?;
+    let base = Url::parse(page).map_err(|e| format!("Invalid page URL '{}': {}", page, e))?;
+
+    let response = reqwest::get(base.clone())
+        .await
+        .map_err(|e| format!("Failed to download '{}': {}", page, e))?;
+    if !response.status().is_success() {
+        return Err(format!("'{}' returned {}", page, response.status()));
+    }
+    let html = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to download '{}': {}", page, e))?;
+
+    let mut links: Vec<PageLink> = Vec::new();
+    for href in HREF.captures_iter(&html) {
+        let Ok(url) = base.join(&href[1].replace("&amp;", "&")) else {
+            continue;
+        };
+        let url = url.to_string();
+        let Some(captures) = pattern.captures(&url) else {
+            continue;
+        };
+        let version = captures
+            .name("version")
+            .or_else(|| captures.get(1))
+            .map(|m| m.as_str().to_string());
+        if !links.iter().any(|l| l.url == url) {
+            links.push(PageLink { url, version });
+        }
+    }
+
+    // max_by keeps the last of equal elements, so search from the end to favour page order
+    links
+        .into_iter()
+        .rev()
+        .max_by(|a, b| match (&a.version, &b.version) {
+            (Some(a), Some(b)) => compare_versions(a, b),
+            _ => Ordering::Equal,
+        })
+        .ok_or_else(|| format!("No link on '{}' matches '{}'", page, pattern))
+}
+
+/// Compares versions by their runs of digits numerically and everything else as text
+///
+/// Text where the other version has a number, or has ended, marks a pre-release, so
+/// `1.2.0-rc1` sorts before both `1.2.0` and `1.2.0.1`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn parts(version: &str) -> Vec<(bool, &str)> {
+        let mut parts = Vec::new();
+        let mut start = 0;
+        for (i, c) in version.char_indices().skip(1) {
+            let prev = version[..i].chars().next_back().unwrap_or_default();
+            if prev.is_ascii_digit() != c.is_ascii_digit() {
+                parts.push(&version[start..i]);
+                start = i;
+            }
+        }
+        parts.push(&version[start..]);
+        parts
+            .into_iter()
+            .filter(|p| !p.is_empty() && !p.chars().all(|c| ".-_+".contains(c)))
+            .map(|p| (p.starts_with(|c: char| c.is_ascii_digit()), p))
+            .collect()
+    }
+
+    let (a, b) = (parts(a), parts(b));
+    for (x, y) in a.iter().zip(&b) {
+        let order = match (x, y) {
+            ((true, x), (true, y)) => x
+                .trim_start_matches('0')
+                .len()
+                .cmp(&y.trim_start_matches('0').len())
+                .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0'))),
+            ((true, _), (false, _)) => Ordering::Greater,
+            ((false, _), (true, _)) => Ordering::Less,
+            ((false, x), (false, y)) => x.to_lowercase().cmp(&y.to_lowercase()),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+
+    // Whatever follows the common part decides: more numbers are a later release, text a
+    // pre-release of it
+    match (a.get(b.len()), b.get(a.len())) {
+        (Some(&(true, _)), _) | (_, Some(&(false, _))) => Ordering::Greater,
+        (Some(&(false, _)), _) | (_, Some(&(true, _))) => Ordering::Less,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_numbers_numerically() {
+        assert_eq!(compare_versions("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("2.0", "10.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.02", "1.2"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2.1", "1.2"), Ordering::Greater);
+    }
+
+    #[test]
+    fn sorts_pre_releases_first() {
+        assert_eq!(compare_versions("1.2.0-rc1", "1.2.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.2.0", "1.2.0-rc1"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2.0-rc1", "1.2.0.1"), Ordering::Less);
+        assert_eq!(
+            compare_versions("1.2.0-rc2", "1.2.0-rc1"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_versions("1.2.0-alpha", "1.2.0-Beta"),
+            Ordering::Less
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use crate::{
+    config::{HomeAccess, SandboxBackend, SandboxProfile},
+    host,
+};
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// The parts of the environment the sandbox arguments depend on
+struct Session {
+    home: Option<PathBuf>,
+    runtime_dir: Option<PathBuf>,
+    wayland_display: Option<OsString>,
+    xauthority: Option<OsString>,
+}
+
+impl Session {
+    fn current() -> Self {
+        Self {
+            home: env::var_os("HOME").map(PathBuf::from),
+            runtime_dir: env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from),
+            wayland_display: env::var_os("WAYLAND_DISPLAY"),
+            xauthority: env::var_os("XAUTHORITY"),
+        }
+    }
+
+    fn expand_home(&self, path: &Path) -> PathBuf {
+        match (path.strip_prefix("~"), &self.home) {
+            (Ok(rest), Some(home)) => home.join(rest),
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+/// Wraps a launch command so it runs under the package's sandbox profile
+///
+/// `exposed` lists paths the app itself needs (the AppImage or its extracted copy)
+pub fn wrap(
+    inner: &Command,
+    profile: &SandboxProfile,
+    exposed: &[PathBuf],
+) -> Result<Command, String> {
+    let bwrap = host::find_executable("bwrap");
+    let firejail = host::find_executable("firejail");
+    let session = Session::current();
+
+    let mut cmd = match (profile.backend, bwrap, firejail) {
+        (SandboxBackend::Auto | SandboxBackend::Bubblewrap, Some(bwrap), _) => {
+            bubblewrap(bwrap, profile, exposed, &session)
+        }
+        (SandboxBackend::Auto | SandboxBackend::Firejail, _, Some(firejail)) => {
+            firejail_command(firejail, profile, exposed, &session)
+        }
+        (SandboxBackend::Bubblewrap, None, _) => {
+            return Err(
+                "This package is sandboxed with bubblewrap, but 'bwrap' is not installed.".into(),
+            );
+        }
+        (SandboxBackend::Firejail, _, None) => {
+            return Err(
+                "This package is sandboxed with firejail, but 'firejail' is not installed.".into(),
+            );
+        }
+        (SandboxBackend::Auto, None, None) => {
+            return Err(
+                "This package is sandboxed, but neither bubblewrap nor firejail is installed."
+                    .into(),
+            );
+        }
+    };
+
+    cmd.arg("--")
+        .arg(inner.get_program())
+        .args(inner.get_args());
+    for (key, value) in inner.get_envs() {
+        match value {
+            Some(value) => cmd.env(key, value),
+            None => cmd.env_remove(key),
+        };
+    }
+    if let Some(dir) = inner.get_current_dir() {
+        cmd.current_dir(dir);
+    }
+    if !profile.display {
+        cmd.env_remove("DISPLAY").env_remove("WAYLAND_DISPLAY");
+    }
+
+    Ok(cmd)
+}
+
+fn bubblewrap(
+    bwrap: PathBuf,
+    profile: &SandboxProfile,
+    exposed: &[PathBuf],
+    session: &Session,
+) -> Command {
+    let mut cmd = Command::new(bwrap);
+    cmd.args(["--die-with-parent", "--unshare-all", "--new-session"]);
+    if profile.network {
+        cmd.arg("--share-net");
+    }
+
+    // Read-only view of the whole system, then mask the private parts
+    cmd.args(["--ro-bind", "/", "/", "--proc", "/proc", "--tmpfs", "/tmp"]);
+    if profile.devices {
+        cmd.args(["--dev-bind", "/dev", "/dev"]);
+    } else {
+        cmd.args(["--dev", "/dev"]);
+    }
+
+    if let Some(home) = &session.home
+        && profile.home == HomeAccess::None
+    {
+        cmd.arg("--tmpfs").arg(home);
+    }
+
+    if let Some(runtime_dir) = &session.runtime_dir {
+        cmd.arg("--tmpfs").arg(runtime_dir);
+    }
+
+    for path in exposed {
+        cmd.arg("--ro-bind").arg(path).arg(path);
+    }
+    for path in &profile.paths {
+        let path = session.expand_home(path);
+        cmd.arg("--bind-try").arg(&path).arg(&path);
+    }
+
+    if profile.display {
+        cmd.args(["--bind-try", "/tmp/.X11-unix", "/tmp/.X11-unix"]);
+        if let Some(xauth) = &session.xauthority {
+            cmd.arg("--ro-bind-try").arg(xauth).arg(xauth);
+        }
+        if let (Some(runtime_dir), Some(wayland)) = (&session.runtime_dir, &session.wayland_display)
+        {
+            let socket = runtime_dir.join(wayland);
+            cmd.arg("--bind-try").arg(&socket).arg(&socket);
+        }
+    }
+
+    cmd
+}
+
+fn firejail_command(
+    firejail: PathBuf,
+    profile: &SandboxProfile,
+    exposed: &[PathBuf],
+    session: &Session,
+) -> Command {
+    let mut cmd = Command::new(firejail);
+    cmd.args(["--quiet", "--noprofile"]);
+
+    if !profile.network {
+        cmd.arg("--net=none");
+    }
+    if !profile.devices {
+        cmd.args(["--private-dev", "--no3d"]);
+    }
+    if !profile.display {
+        cmd.arg("--x11=none");
+        // firejail has no switch for Wayland, so the compositor sockets are hidden instead
+        if let Some(runtime_dir) = &session.runtime_dir {
+            cmd.arg(format!(
+                "--blacklist={}",
+                runtime_dir.join("wayland-*").to_string_lossy()
+            ));
+            if let Some(wayland) = &session.wayland_display {
+                cmd.arg(format!(
+                    "--blacklist={}",
+                    runtime_dir.join(wayland).to_string_lossy()
+                ));
+            }
+        }
+    }
+
+    match profile.home {
+        HomeAccess::None => {
+            let (in_home, outside): (Vec<PathBuf>, Vec<PathBuf>) = exposed
+                .iter()
+                .cloned()
+                .chain(profile.paths.iter().map(|p| session.expand_home(p)))
+                .partition(|p| {
+                    session
+                        .home
+                        .as_ref()
+                        .is_some_and(|home| p.starts_with(home))
+                });
+
+            // Whitelisting paths in the home directory replaces it with a tmpfs holding only
+            // them; without any, --private hides it. Paths elsewhere (e.g. /opt/axe in system
+            // mode) stay visible as they are, whitelisting them would hide their siblings.
+            if in_home.is_empty() {
+                cmd.arg("--private");
+            }
+            for path in in_home {
+                cmd.arg(format!("--whitelist={}", path.to_string_lossy()));
+            }
+            for path in outside.iter().filter(|p| exposed.contains(p)) {
+                cmd.arg(format!("--read-only={}", path.to_string_lossy()));
+            }
+        }
+        HomeAccess::ReadOnly => {
+            cmd.arg("--read-only=${HOME}");
+            for path in &profile.paths {
+                cmd.arg(format!(
+                    "--read-write={}",
+                    session.expand_home(path).to_string_lossy()
+                ));
+            }
+        }
+    }
+
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        Session {
+            home: Some("/home/me".into()),
+            runtime_dir: Some("/run/user/1000".into()),
+            wayland_display: Some("wayland-1".into()),
+            xauthority: Some("/home/me/.Xauthority".into()),
+        }
+    }
+
+    fn profile(home: HomeAccess, open: bool, paths: &[&str]) -> SandboxProfile {
+        SandboxProfile {
+            backend: SandboxBackend::Auto,
+            network: open,
+            home,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            devices: open,
+            display: open,
+        }
+    }
+
+    fn argv(cmd: Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn bwrap(profile: &SandboxProfile, exposed: &[&str]) -> Vec<String> {
+        let exposed: Vec<PathBuf> = exposed.iter().map(PathBuf::from).collect();
+        argv(bubblewrap("bwrap".into(), profile, &exposed, &session()))
+    }
+
+    fn firejail(profile: &SandboxProfile, exposed: &[&str]) -> Vec<String> {
+        let exposed: Vec<PathBuf> = exposed.iter().map(PathBuf::from).collect();
+        argv(firejail_command(
+            "firejail".into(),
+            profile,
+            &exposed,
+            &session(),
+        ))
+    }
+
+    #[test]
+    fn bubblewrap_hides_home_and_display() {
+        let profile = profile(HomeAccess::None, false, &["~/Documents"]);
+        assert_eq!(
+            bwrap(&profile, &["/home/me/.local/share/axe/bin/app"]),
+            [
+                "--die-with-parent",
+                "--unshare-all",
+                "--new-session",
+                "--ro-bind",
+                "/",
+                "/",
+                "--proc",
+                "/proc",
+                "--tmpfs",
+                "/tmp",
+                "--dev",
+                "/dev",
+                "--tmpfs",
+                "/home/me",
+                "--tmpfs",
+                "/run/user/1000",
+                "--ro-bind",
+                "/home/me/.local/share/axe/bin/app",
+                "/home/me/.local/share/axe/bin/app",
+                "--bind-try",
+                "/home/me/Documents",
+                "/home/me/Documents",
+            ]
+        );
+    }
+
+    #[test]
+    fn bubblewrap_shares_what_the_profile_allows() {
+        let profile = profile(HomeAccess::ReadOnly, true, &[]);
+        assert_eq!(
+            bwrap(&profile, &["/opt/axe/bin/app"]),
+            [
+                "--die-with-parent",
+                "--unshare-all",
+                "--new-session",
+                "--share-net",
+                "--ro-bind",
+                "/",
+                "/",
+                "--proc",
+                "/proc",
+                "--tmpfs",
+                "/tmp",
+                "--dev-bind",
+                "/dev",
+                "/dev",
+                "--tmpfs",
+                "/run/user/1000",
+                "--ro-bind",
+                "/opt/axe/bin/app",
+                "/opt/axe/bin/app",
+                "--bind-try",
+                "/tmp/.X11-unix",
+                "/tmp/.X11-unix",
+                "--ro-bind-try",
+                "/home/me/.Xauthority",
+                "/home/me/.Xauthority",
+                "--bind-try",
+                "/run/user/1000/wayland-1",
+                "/run/user/1000/wayland-1",
+            ]
+        );
+    }
+
+    #[test]
+    fn firejail_hides_home_and_wayland() {
+        let profile = profile(HomeAccess::None, false, &[]);
+        assert_eq!(
+            firejail(&profile, &["/opt/axe/bin/app"]),
+            [
+                "--quiet",
+                "--noprofile",
+                "--net=none",
+                "--private-dev",
+                "--no3d",
+                "--x11=none",
+                "--blacklist=/run/user/1000/wayland-*",
+                "--blacklist=/run/user/1000/wayland-1",
+                "--private",
+                "--read-only=/opt/axe/bin/app",
+            ]
+        );
+    }
+
+    #[test]
+    fn firejail_whitelists_paths_in_home() {
+        let profile = profile(HomeAccess::None, true, &["~/Documents"]);
+        assert_eq!(
+            firejail(&profile, &["/home/me/.local/share/axe/bin/app"]),
+            [
+                "--quiet",
+                "--noprofile",
+                "--whitelist=/home/me/.local/share/axe/bin/app",
+                "--whitelist=/home/me/Documents",
+            ]
+        );
+    }
+
+    #[test]
+    fn firejail_read_only_home() {
+        let profile = profile(HomeAccess::ReadOnly, true, &["~/Documents"]);
+        assert_eq!(
+            firejail(&profile, &["/home/me/.local/share/axe/bin/app"]),
+            [
+                "--quiet",
+                "--noprofile",
+                "--read-only=${HOME}",
+                "--read-write=/home/me/Documents",
+            ]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{appimage, host};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+const SIGNATURE_SECTION: &str = ".sha256_sig";
+const KEY_SECTION: &str = ".sig_key";
+
+pub enum Signature {
+    /// The AppImage carries no signature
+    Unsigned,
+    /// Signed, but the signature could not be checked (no gpg, unknown key)
+    Unverified(String),
+    /// Valid signature by the key with this primary fingerprint
+    Valid { fingerprint: String, trusted: bool },
+}
+
+/// Verifies the GPG signature embedded in a type 2 AppImage
+///
+/// The signature covers the hex SHA-256 digest of the file with the signature and key
+/// sections zeroed. It is checked against the embedded key and `trusted_keys`, a list of
+/// armored public key files; `trusted` tells whether the signer is one of the latter.
+pub fn verify(path: &Path, trusted_keys: &[PathBuf]) -> Result<Signature, String> {
+    let Some(sig_range) = appimage::section_range(path, SIGNATURE_SECTION)? else {
+        return Ok(Signature::Unsigned);
+    };
+    let signature = section_text(path, SIGNATURE_SECTION)?;
+    if signature.is_empty() {
+        return Ok(Signature::Unsigned);
+    }
+
+    let Some(gpg) = host::find_executable("gpg") else {
+        return Ok(Signature::Unverified(
+            "the AppImage is signed, but gpg is not installed to verify it".into(),
+        ));
+    };
+
+    let key_range = appimage::section_range(path, KEY_SECTION)?;
+    let key = section_text(path, KEY_SECTION)?;
+    let digest = digest(path, &[Some(sig_range), key_range])?;
+
+    // A fresh keyring for every check, created private (0700) and removed when dropped
+    let home = tempfile::Builder::new()
+        .prefix("axe-gpg-")
+        .tempdir()
+        .map_err(|e| format!("Failed to create a GPG home: {}", e))?;
+    run_gpg(&gpg, home.path(), &signature, &key, &digest, trusted_keys)
+}
+
+fn run_gpg(
+    gpg: &Path,
+    home: &Path,
+    signature: &str,
+    key: &str,
+    digest: &str,
+    trusted_keys: &[PathBuf],
+) -> Result<Signature, String> {
+    let gpg = || {
+        let mut cmd = Command::new(gpg);
+        cmd.arg("--homedir")
+            .arg(home)
+            .args(["--batch", "--no-tty"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null());
+        cmd
+    };
+
+    let mut trusted = Vec::new();
+    for key_file in trusted_keys {
+        let output = gpg()
+            .args(["--with-colons", "--import-options", "show-only", "--import"])
+            .arg(key_file)
+            .output()
+            .map_err(|e| format!("Failed to run gpg: {}", e))?;
+        trusted.extend(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|l| l.strip_prefix("fpr:"))
+                .filter_map(|l| l.trim_end_matches(':').rsplit(':').next())
+                .map(str::to_uppercase),
+        );
+        let status = gpg()
+            .args(["--quiet", "--import"])
+            .arg(key_file)
+            .status()
+            .map_err(|e| format!("Failed to run gpg: {}", e))?;
+        if !status.success() {
+            return Err(format!("Failed to import trusted key {:?}", key_file));
+        }
+    }
+
+    if !key.is_empty() {
+        let key_file = home.join("key.asc");
+        fs::write(&key_file, key).map_err(|e| e.to_string())?;
+        let status = gpg()
+            .args(["--quiet", "--import"])
+            .arg(&key_file)
+            .status()
+            .map_err(|e| format!("Failed to run gpg: {}", e))?;
+        // Without the embedded key gpg can still verify against a trusted one
+        if !status.success() && trusted.is_empty() {
+            return Ok(Signature::Unverified(
+                "the key embedded in the AppImage could not be imported".into(),
+            ));
+        }
+    }
+
+    let sig_file = home.join("digest.sig");
+    let digest_file = home.join("digest");
+    fs::write(&sig_file, signature).map_err(|e| e.to_string())?;
+    fs::write(&digest_file, digest).map_err(|e| e.to_string())?;
+
+    let output = gpg()
+        .args(["--status-fd", "1", "--verify"])
+        .arg(&sig_file)
+        .arg(&digest_file)
+        .output()
+        .map_err(|e| format!("Failed to run gpg: {}", e))?;
+    parse_status(
+        &String::from_utf8_lossy(&output.stdout),
+        output.status.success(),
+        &trusted,
+    )
+}
+
+/// Interprets the `--status-fd` output of `gpg --verify`
+fn parse_status(status: &str, success: bool, trusted: &[String]) -> Result<Signature, String> {
+    for line in status.lines() {
+        let Some(rest) = line.strip_prefix("[GNUPG:] ") else {
+            continue;
+        };
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        match fields.as_slice() {
+            // The last field is the fingerprint of the primary key
+            ["VALIDSIG", .., primary] if success => {
+                let fingerprint = primary.to_uppercase();
+                return Ok(Signature::Valid {
+                    trusted: trusted.contains(&fingerprint),
+                    fingerprint,
+                });
+            }
+            ["BADSIG", ..] => {
+                return Err("The embedded signature does not match the AppImage".into());
+            }
+            // Signatures by expired or revoked keys and ones gpg can't process aren't proof
+            // of a tampered file, but they don't vouch for it either
+            ["EXPKEYSIG", key_id, ..] => {
+                return Ok(Signature::Unverified(format!(
+                    "the AppImage is signed by expired key {}",
+                    key_id
+                )));
+            }
+            ["REVKEYSIG", key_id, ..] => {
+                return Ok(Signature::Unverified(format!(
+                    "the AppImage is signed by revoked key {}",
+                    key_id
+                )));
+            }
+            ["ERRSIG", key_id, ..] if !status.contains("NO_PUBKEY") => {
+                return Ok(Signature::Unverified(format!(
+                    "gpg could not check the signature by key {}",
+                    key_id
+                )));
+            }
+            ["NO_PUBKEY", key_id] => {
+                return Ok(Signature::Unverified(format!(
+                    "the AppImage is signed by unknown key {}, add it to trusted_keys to verify it",
+                    key_id
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    Err("The embedded signature could not be verified".into())
+}
+
+/// Reads a section as text, without the zero padding
+fn section_text(path: &Path, name: &str) -> Result<String, String> {
+    let data = appimage::read_section(path, name)?.unwrap_or_default();
+    Ok(String::from_utf8_lossy(&data)
+        .trim_matches(char::from(0))
+        .trim()
+        .to_string())
+}
+
+/// Hex SHA-256 of the file with the given byte ranges read as zeros
+fn digest(path: &Path, zeroed: &[Option<(u64, u64)>]) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut position = 0u64;
+
+    loop {
+        let count = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if count == 0 {
+            break;
+        }
+        let chunk = &mut buffer[..count];
+        let end = position + count as u64;
+        for &(offset, size) in zeroed.iter().flatten() {
+            let start = offset.max(position);
+            let stop = (offset + size).min(end);
+            if start < stop {
+                chunk[(start - position) as usize..(stop - position) as usize].fill(0);
+            }
+        }
+        hasher.update(&*chunk);
+        position = end;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINGERPRINT: &str = "0123456789ABCDEF0123456789ABCDEF01234567";
+
+    fn parse(lines: &[&str], success: bool) -> Result<Signature, String> {
+        let status: String = lines.iter().map(|l| format!("[GNUPG:] {}\n", l)).collect();
+        parse_status(&status, success, &[FINGERPRINT.to_string()])
+    }
+
+    fn unverified(result: Result<Signature, String>) -> String {
+        match result {
+            Ok(Signature::Unverified(reason)) => reason,
+            _ => panic!("expected an unverified signature"),
+        }
+    }
+
+    #[test]
+    fn valid_signatures() {
+        let validsig = format!(
+            "VALIDSIG 89ABCDEF89ABCDEF89ABCDEF89ABCDEF89ABCDEF 2024-01-01 1704067200 0 4 0 1 8 00 {}",
+            FINGERPRINT.to_lowercase()
+        );
+        let lines = [
+            "NEWSIG",
+            "GOODSIG 0123456789ABCDEF Vendor",
+            validsig.as_str(),
+        ];
+        match parse(&lines, true) {
+            Ok(Signature::Valid {
+                fingerprint,
+                trusted,
+            }) => {
+                assert_eq!(fingerprint, FINGERPRINT);
+                assert!(trusted);
+            }
+            _ => panic!("expected a valid signature"),
+        }
+        // gpg failing overall outweighs a VALIDSIG line
+        assert!(parse(&lines, false).is_err());
+    }
+
+    #[test]
+    fn bad_signatures() {
+        assert!(parse(&["NEWSIG", "BADSIG 0123456789ABCDEF Vendor"], false).is_err());
+        assert!(parse(&[], false).is_err());
+    }
+
+    #[test]
+    fn signatures_that_prove_nothing() {
+        let expired = parse(&["EXPKEYSIG 0123456789ABCDEF Vendor"], false);
+        assert!(unverified(expired).contains("expired key 0123456789ABCDEF"));
+        let revoked = parse(&["REVKEYSIG 0123456789ABCDEF Vendor"], false);
+        assert!(unverified(revoked).contains("revoked key 0123456789ABCDEF"));
+        let error = parse(&["ERRSIG 0123456789ABCDEF 1 8 00 1704067200 4 -"], false);
+        assert!(unverified(error).contains("could not check"));
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let lines = [
+            "ERRSIG 0123456789ABCDEF 1 8 00 1704067200 9 -",
+            "NO_PUBKEY 0123456789ABCDEF",
+        ];
+        assert!(unverified(parse(&lines, false)).contains("unknown key 0123456789ABCDEF"));
+    }
+
+    #[test]
+    fn digest_zeroes_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app");
+        // Larger than the read buffer, with a range spanning two reads
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8 + 1).collect();
+        fs::write(&path, &data).unwrap();
+
+        let ranges = [Some((10, 20)), None, Some((65_000, 2_000))];
+        let mut zeroed = data.clone();
+        zeroed[10..30].fill(0);
+        zeroed[65_000..67_000].fill(0);
+
+        assert_eq!(
+            digest(&path, &ranges).unwrap(),
+            hex::encode(Sha256::digest(&zeroed))
+        );
+        assert_eq!(
+            digest(&path, &[None]).unwrap(),
+            hex::encode(Sha256::digest(&data))
+        );
+    }
+}
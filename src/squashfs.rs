@@ -0,0 +1,587 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use flate2::read::ZlibDecoder;
+
+const MAGIC: &[u8; 4] = b"hsqs";
+const SUPERBLOCK_SIZE: u64 = 96;
+
+/// Uncompressed size of a metadata block
+const METADATA_SIZE: usize = 8192;
+const UNCOMPRESSED_METADATA: u16 = 0x8000;
+const UNCOMPRESSED_BLOCK: u32 = 1 << 24;
+const NO_FRAGMENT: u32 = u32::MAX;
+const FRAGMENTS_PER_BLOCK: u32 = (METADATA_SIZE / 16) as u32;
+
+/// Largest file read from an image, far above any desktop file
+const MAX_FILE_SIZE: u64 = 1 << 20;
+
+const INODE_HEADER_SIZE: usize = 16;
+const BASIC_DIR: u16 = 1;
+const BASIC_FILE: u16 = 2;
+const BASIC_SYMLINK: u16 = 3;
+const EXT_DIR: u16 = 8;
+const EXT_FILE: u16 = 9;
+const EXT_SYMLINK: u16 = 10;
+
+#[derive(Clone, Copy)]
+enum Compressor {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+enum Inode {
+    Dir {
+        block: u32,
+        offset: u16,
+        size: u32,
+    },
+    File {
+        blocks_start: u64,
+        size: u64,
+        fragment: u32,
+        fragment_offset: u32,
+        block_sizes: Vec<u32>,
+    },
+    Symlink(String),
+    Other,
+}
+
+struct DirEntry {
+    name: String,
+    inode: u64,
+}
+
+/// A read-only view of a SquashFS 4.0 image starting at `base` in `reader`
+///
+/// Only what is needed to read small files from the root directory is supported: no
+/// subdirectories, xattrs or LZO/LZ4 compression.
+pub struct Image<R> {
+    reader: R,
+    base: u64,
+    len: u64,
+    block_size: u32,
+    compressor: Compressor,
+    fragment_count: u32,
+    root_inode: u64,
+    inode_table: u64,
+    directory_table: u64,
+    fragment_table: u64,
+}
+
+impl<R: Read + Seek> Image<R> {
+    /// Reads the superblock at `base`, where `len` is the size of the whole file
+    pub fn open(mut reader: R, base: u64, len: u64) -> Result<Self, String> {
+        let len = len
+            .checked_sub(base)
+            .ok_or("The image starts past the end of the file")?;
+        if len < SUPERBLOCK_SIZE {
+            return Err("File is too small to hold a SquashFS image".into());
+        }
+        let mut sb = [0u8; SUPERBLOCK_SIZE as usize];
+        reader
+            .seek(SeekFrom::Start(base))
+            .and_then(|_| reader.read_exact(&mut sb))
+            .map_err(|e| format!("Failed to read the SquashFS superblock: {}", e))?;
+
+        if &sb[..4] != MAGIC {
+            return Err("No SquashFS image found".into());
+        }
+        if u16_at(&sb, 28) != 4 {
+            return Err(format!("Unsupported SquashFS version {}", u16_at(&sb, 28)));
+        }
+        let block_size = u32_at(&sb, 12);
+        if !block_size.is_power_of_two()
+            || !(4096..=1 << 20).contains(&block_size)
+            || 1u32 << u16_at(&sb, 22) != block_size
+        {
+            return Err(format!("Invalid SquashFS block size {}", block_size));
+        }
+        let compressor = match u16_at(&sb, 20) {
+            1 => Compressor::Gzip,
+            4 => Compressor::Xz,
+            6 => Compressor::Zstd,
+            id => return Err(format!("Unsupported SquashFS compression {}", id)),
+        };
+
+        Ok(Self {
+            reader,
+            base,
+            len,
+            block_size,
+            compressor,
+            fragment_count: u32_at(&sb, 16),
+            root_inode: u64_at(&sb, 32),
+            inode_table: u64_at(&sb, 64),
+            directory_table: u64_at(&sb, 72),
+            fragment_table: u64_at(&sb, 80),
+        })
+    }
+
+    /// Reads the first file in the root directory whose name matches, following a symlink
+    /// to another file in the root directory
+    pub fn find_root_file(
+        &mut self,
+        matches: impl Fn(&str) -> bool,
+    ) -> Result<Option<(String, Vec<u8>)>, String> {
+        let entries = match self.inode(self.root_inode)? {
+            Inode::Dir {
+                block,
+                offset,
+                size,
+            } => self.read_dir(block, offset, size)?,
+            _ => return Err("The SquashFS root is not a directory".into()),
+        };
+
+        let Some(entry) = entries.iter().find(|e| matches(&e.name)) else {
+            return Ok(None);
+        };
+        let inode = match self.inode(entry.inode)? {
+            Inode::Symlink(target) => {
+                match entries
+                    .iter()
+                    .find(|e| e.name == target.trim_start_matches("./"))
+                {
+                    Some(target) => self.inode(target.inode)?,
+                    None => return Ok(None),
+                }
+            }
+            inode => inode,
+        };
+
+        match inode {
+            Inode::File {
+                blocks_start,
+                size,
+                fragment,
+                fragment_offset,
+                block_sizes,
+            } => {
+                let data =
+                    self.read_file(blocks_start, size, fragment, fragment_offset, &block_sizes)?;
+                Ok(Some((entry.name.clone(), data)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn inode(&mut self, reference: u64) -> Result<Inode, String> {
+        let start = self.inode_table + (reference >> 16);
+        let skip = (reference & 0xFFFF) as usize;
+        let header = self.read_metadata(start, skip, INODE_HEADER_SIZE)?;
+        let body_size = match u16_at(&header, 0) {
+            BASIC_DIR => 16,
+            EXT_DIR => 24,
+            BASIC_FILE => 16,
+            EXT_FILE => 40,
+            BASIC_SYMLINK | EXT_SYMLINK => 8,
+            _ => return Ok(Inode::Other),
+        };
+        let skip = skip + INODE_HEADER_SIZE;
+        let body = self.read_metadata(start, skip, body_size)?;
+
+        match u16_at(&header, 0) {
+            BASIC_DIR => Ok(Inode::Dir {
+                block: u32_at(&body, 0),
+                offset: u16_at(&body, 10),
+                size: u16_at(&body, 8) as u32,
+            }),
+            EXT_DIR => Ok(Inode::Dir {
+                block: u32_at(&body, 8),
+                offset: u16_at(&body, 18),
+                size: u32_at(&body, 4),
+            }),
+            BASIC_SYMLINK | EXT_SYMLINK => {
+                let target_size = u32_at(&body, 4) as usize;
+                if target_size > 4096 {
+                    return Err("SquashFS symlink target is too long".into());
+                }
+                let target = self.read_metadata(start, skip + body_size, target_size)?;
+                Ok(Inode::Symlink(
+                    String::from_utf8_lossy(&target).into_owned(),
+                ))
+            }
+            kind => {
+                let (blocks_start, size, fragment, fragment_offset) = if kind == BASIC_FILE {
+                    (
+                        u32_at(&body, 0) as u64,
+                        u32_at(&body, 12) as u64,
+                        u32_at(&body, 4),
+                        u32_at(&body, 8),
+                    )
+                } else {
+                    (
+                        u64_at(&body, 0),
+                        u64_at(&body, 8),
+                        u32_at(&body, 28),
+                        u32_at(&body, 32),
+                    )
+                };
+                if size > MAX_FILE_SIZE {
+                    return Err(format!("SquashFS file is too large ({} bytes)", size));
+                }
+                let block_size = self.block_size as u64;
+                let count = if fragment == NO_FRAGMENT {
+                    size.div_ceil(block_size)
+                } else {
+                    size / block_size
+                } as usize;
+                let list = self.read_metadata(start, skip + body_size, count * 4)?;
+                Ok(Inode::File {
+                    blocks_start,
+                    size,
+                    fragment,
+                    fragment_offset,
+                    block_sizes: list.chunks_exact(4).map(|b| u32_at(b, 0)).collect(),
+                })
+            }
+        }
+    }
+
+    fn read_dir(&mut self, block: u32, offset: u16, size: u32) -> Result<Vec<DirEntry>, String> {
+        // The recorded size counts the "." and ".." entries, which are not stored
+        let Some(size) = (size as usize).checked_sub(3) else {
+            return Ok(Vec::new());
+        };
+        let data =
+            self.read_metadata(self.directory_table + block as u64, offset as usize, size)?;
+
+        let truncated = || "Truncated SquashFS directory".to_string();
+        let mut entries = Vec::new();
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            let header = rest.get(..12).ok_or_else(truncated)?;
+            let count = u32_at(header, 0) as usize + 1;
+            let inode_block = u32_at(header, 4) as u64;
+            rest = &rest[12..];
+            for _ in 0..count {
+                let entry = rest.get(..8).ok_or_else(truncated)?;
+                let name_size = u16_at(entry, 6) as usize + 1;
+                let name = rest.get(8..8 + name_size).ok_or_else(truncated)?;
+                entries.push(DirEntry {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    inode: inode_block << 16 | u16_at(entry, 0) as u64,
+                });
+                rest = &rest[8 + name_size..];
+            }
+        }
+        Ok(entries)
+    }
+
+    fn read_file(
+        &mut self,
+        blocks_start: u64,
+        size: u64,
+        fragment: u32,
+        fragment_offset: u32,
+        block_sizes: &[u32],
+    ) -> Result<Vec<u8>, String> {
+        let block_size = self.block_size as usize;
+        let mut data = Vec::with_capacity(size as usize);
+        let mut position = blocks_start;
+
+        for &entry in block_sizes {
+            let want = block_size.min(size as usize - data.len());
+            let stored = entry & !UNCOMPRESSED_BLOCK;
+            if stored == 0 {
+                data.resize(data.len() + want, 0);
+                continue;
+            }
+            let block = self.read_block(position, entry)?;
+            position += stored as u64;
+            data.extend_from_slice(block.get(..want).ok_or("Truncated SquashFS data block")?);
+        }
+
+        if fragment != NO_FRAGMENT {
+            if fragment >= self.fragment_count {
+                return Err("SquashFS fragment index out of range".into());
+            }
+            let pointer = self.read_raw(
+                self.fragment_table + (fragment / FRAGMENTS_PER_BLOCK) as u64 * 8,
+                8,
+            )?;
+            let entry = self.read_metadata(
+                u64_at(&pointer, 0),
+                (fragment % FRAGMENTS_PER_BLOCK) as usize * 16,
+                16,
+            )?;
+            let block = self.read_block(u64_at(&entry, 0), u32_at(&entry, 8))?;
+            let start = fragment_offset as usize;
+            let tail = size as usize - data.len();
+            data.extend_from_slice(
+                block
+                    .get(start..start + tail)
+                    .ok_or("Truncated SquashFS fragment")?,
+            );
+        }
+
+        if data.len() as u64 != size {
+            return Err("Truncated SquashFS file".into());
+        }
+        Ok(data)
+    }
+
+    /// Reads a data or fragment block given its on-disk size entry
+    fn read_block(&mut self, position: u64, entry: u32) -> Result<Vec<u8>, String> {
+        let stored = entry & !UNCOMPRESSED_BLOCK;
+        if stored > self.block_size {
+            return Err("SquashFS data block is larger than the block size".into());
+        }
+        let raw = self.read_raw(position, stored as u64)?;
+        if entry & UNCOMPRESSED_BLOCK != 0 {
+            Ok(raw)
+        } else {
+            self.decompress(&raw, self.block_size as usize)
+        }
+    }
+
+    /// Reads `len` bytes of metadata, `skip` bytes into the metadata block at `start`
+    fn read_metadata(&mut self, start: u64, skip: usize, len: usize) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        let mut position = start;
+        while data.len() < skip + len {
+            let header = self.read_raw(position, 2)?;
+            let header = u16_at(&header, 0);
+            let size = (header & !UNCOMPRESSED_METADATA) as usize;
+            if size > METADATA_SIZE {
+                return Err("SquashFS metadata block is too large".into());
+            }
+            let raw = self.read_raw(position + 2, size as u64)?;
+            position += 2 + size as u64;
+            if header & UNCOMPRESSED_METADATA != 0 {
+                data.extend_from_slice(&raw);
+            } else {
+                data.extend_from_slice(&self.decompress(&raw, METADATA_SIZE)?);
+            }
+        }
+        Ok(data[skip..skip + len].to_vec())
+    }
+
+    /// Reads `size` bytes at `position` in the image, refusing ranges past its end
+    fn read_raw(&mut self, position: u64, size: u64) -> Result<Vec<u8>, String> {
+        if position.checked_add(size).is_none_or(|end| end > self.len) {
+            return Err("SquashFS data lies outside the file".into());
+        }
+        let mut buf = vec![0u8; size as usize];
+        self.reader
+            .seek(SeekFrom::Start(self.base + position))
+            .and_then(|_| self.reader.read_exact(&mut buf))
+            .map_err(|e| format!("Failed to read the SquashFS image: {}", e))?;
+        Ok(buf)
+    }
+
+    fn decompress(&self, data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+        let mut out = Limited {
+            data: Vec::new(),
+            limit,
+        };
+        let result = match self.compressor {
+            Compressor::Gzip => io::copy(&mut ZlibDecoder::new(data), &mut out).map(|_| ()),
+            Compressor::Zstd => ruzstd::decoding::StreamingDecoder::new(data)
+                .map_err(|e| io::Error::other(e.to_string()))
+                .and_then(|mut d| io::copy(&mut d, &mut out).map(|_| ())),
+            Compressor::Xz => lzma_rs::xz_decompress(&mut &data[..], &mut out)
+                .map_err(|e| io::Error::other(e.to_string())),
+        };
+        result.map_err(|e| format!("Failed to decompress SquashFS block: {}", e))?;
+        Ok(out.data)
+    }
+}
+
+/// A buffer that refuses to grow past `limit`, so a crafted block cannot exhaust memory
+struct Limited {
+    data: Vec<u8>,
+    limit: usize,
+}
+
+impl Write for Limited {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.data.len() + buf.len() > self.limit {
+            return Err(io::Error::other("block is larger than expected"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn u16_at(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn u32_at(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::ZlibEncoder};
+    use std::io::Cursor;
+
+    const BASE: u64 = 100;
+    const BLOCK_SIZE: u32 = 4096;
+    const DESKTOP: &[u8] = b"[Desktop Entry]\nName=Demo\n";
+
+    fn metadata_block(data: &[u8], compress: bool) -> Vec<u8> {
+        let (header, data) = if compress {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).unwrap();
+            let data = encoder.finish().unwrap();
+            (data.len() as u16, data)
+        } else {
+            (data.len() as u16 | UNCOMPRESSED_METADATA, data.to_vec())
+        };
+        let mut block = header.to_le_bytes().to_vec();
+        block.extend_from_slice(&data);
+        block
+    }
+
+    fn inode_header(kind: u16, number: u32) -> Vec<u8> {
+        let mut header = vec![0u8; INODE_HEADER_SIZE];
+        header[..2].copy_from_slice(&kind.to_le_bytes());
+        header[12..].copy_from_slice(&number.to_le_bytes());
+        header
+    }
+
+    /// An image whose root holds `a.txt` in a full data block, `share.desktop` in a
+    /// fragment and `app.desktop`, a symlink to `share.desktop`
+    fn image() -> Vec<u8> {
+        let large = vec![b'x'; BLOCK_SIZE as usize];
+        let data_at = SUPERBLOCK_SIZE;
+        let fragment_at = data_at + large.len() as u64;
+        let inodes_at = fragment_at + DESKTOP.len() as u64;
+
+        let names = [
+            ("a.txt", 2u16, 32u16),
+            ("app.desktop", 3, 68),
+            ("share.desktop", 2, 105),
+        ];
+        let mut listing = Vec::new();
+        listing.extend_from_slice(&(names.len() as u32 - 1).to_le_bytes());
+        listing.extend_from_slice(&0u32.to_le_bytes());
+        listing.extend_from_slice(&2u32.to_le_bytes());
+        for (i, (name, kind, offset)) in names.iter().enumerate() {
+            listing.extend_from_slice(&offset.to_le_bytes());
+            listing.extend_from_slice(&(i as i16).to_le_bytes());
+            listing.extend_from_slice(&kind.to_le_bytes());
+            listing.extend_from_slice(&(name.len() as u16 - 1).to_le_bytes());
+            listing.extend_from_slice(name.as_bytes());
+        }
+
+        let mut inodes = inode_header(BASIC_DIR, 1);
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        inodes.extend_from_slice(&2u32.to_le_bytes());
+        inodes.extend_from_slice(&(listing.len() as u16 + 3).to_le_bytes());
+        inodes.extend_from_slice(&0u16.to_le_bytes());
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!(inodes.len(), 32);
+
+        inodes.extend(inode_header(BASIC_FILE, 2));
+        inodes.extend_from_slice(&(data_at as u32).to_le_bytes());
+        inodes.extend_from_slice(&NO_FRAGMENT.to_le_bytes());
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        inodes.extend_from_slice(&BLOCK_SIZE.to_le_bytes());
+        inodes.extend_from_slice(&(BLOCK_SIZE | UNCOMPRESSED_BLOCK).to_le_bytes());
+        assert_eq!(inodes.len(), 68);
+
+        let target = b"share.desktop";
+        inodes.extend(inode_header(BASIC_SYMLINK, 3));
+        inodes.extend_from_slice(&1u32.to_le_bytes());
+        inodes.extend_from_slice(&(target.len() as u32).to_le_bytes());
+        inodes.extend_from_slice(target);
+        assert_eq!(inodes.len(), 105);
+
+        inodes.extend(inode_header(BASIC_FILE, 4));
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        inodes.extend_from_slice(&0u32.to_le_bytes());
+        inodes.extend_from_slice(&(DESKTOP.len() as u32).to_le_bytes());
+
+        let inode_table = metadata_block(&inodes, true);
+        let directories_at = inodes_at + inode_table.len() as u64;
+        let directory_table = metadata_block(&listing, false);
+        let fragment_entries_at = directories_at + directory_table.len() as u64;
+        let mut fragment_entry = fragment_at.to_le_bytes().to_vec();
+        fragment_entry
+            .extend_from_slice(&(DESKTOP.len() as u32 | UNCOMPRESSED_BLOCK).to_le_bytes());
+        fragment_entry.extend_from_slice(&0u32.to_le_bytes());
+        let fragment_entries = metadata_block(&fragment_entry, false);
+        let fragment_table_at = fragment_entries_at + fragment_entries.len() as u64;
+
+        let mut sb = vec![0u8; SUPERBLOCK_SIZE as usize];
+        sb[..4].copy_from_slice(MAGIC);
+        sb[4..8].copy_from_slice(&4u32.to_le_bytes());
+        sb[12..16].copy_from_slice(&BLOCK_SIZE.to_le_bytes());
+        sb[16..20].copy_from_slice(&1u32.to_le_bytes());
+        sb[20..22].copy_from_slice(&1u16.to_le_bytes());
+        sb[22..24].copy_from_slice(&12u16.to_le_bytes());
+        sb[28..30].copy_from_slice(&4u16.to_le_bytes());
+        sb[64..72].copy_from_slice(&inodes_at.to_le_bytes());
+        sb[72..80].copy_from_slice(&directories_at.to_le_bytes());
+        sb[80..88].copy_from_slice(&fragment_table_at.to_le_bytes());
+
+        let mut file = vec![0u8; BASE as usize];
+        file.extend(sb);
+        file.extend(large);
+        file.extend_from_slice(DESKTOP);
+        file.extend(inode_table);
+        file.extend(directory_table);
+        file.extend(fragment_entries);
+        file.extend_from_slice(&fragment_entries_at.to_le_bytes());
+        file
+    }
+
+    fn find(file: &[u8], name: &str) -> Result<Option<(String, Vec<u8>)>, String> {
+        Image::open(Cursor::new(file), BASE, file.len() as u64)?.find_root_file(|n| n == name)
+    }
+
+    #[test]
+    fn reads_files_from_data_blocks() {
+        let (name, data) = find(&image(), "a.txt").unwrap().unwrap();
+        assert_eq!(name, "a.txt");
+        assert_eq!(data, vec![b'x'; BLOCK_SIZE as usize]);
+    }
+
+    #[test]
+    fn reads_files_from_fragments() {
+        let file = image();
+        let found = find(&file, "share.desktop").unwrap();
+        assert_eq!(found, Some(("share.desktop".into(), DESKTOP.to_vec())));
+    }
+
+    #[test]
+    fn follows_symlinks_in_the_root() {
+        let file = image();
+        let mut image = Image::open(Cursor::new(&file), BASE, file.len() as u64).unwrap();
+        let found = image.find_root_file(|n| n.ends_with(".desktop")).unwrap();
+        assert_eq!(found, Some(("app.desktop".into(), DESKTOP.to_vec())));
+    }
+
+    #[test]
+    fn missing_files() {
+        assert_eq!(find(&image(), "b.txt"), Ok(None));
+    }
+
+    #[test]
+    fn rejects_other_data() {
+        let mut file = image();
+        file[BASE as usize] = b'x';
+        assert!(find(&file, "a.txt").is_err());
+        assert!(Image::open(Cursor::new(&file), 0, file.len() as u64).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_images() {
+        let file = image();
+        assert!(find(&file[..file.len() - 4], "share.desktop").is_err());
+        assert!(find(&file[..BASE as usize + 200], "a.txt").is_err());
+    }
+}